@@ -0,0 +1,7 @@
+pub mod control_groups;
+pub mod camera;
+pub mod ending;
+pub mod mechanics;
+pub mod commands;
+pub mod abilities;
+pub mod macro_metrics;
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+// ability table of the build the protocol decodes
+const EMBEDDED_ABILITIES: &str = include_str!("abilities.csv");
+
+// readable names for the commands of catalog abilities, by command index.
+// empty names are commands that aren't worth naming
+const COMMAND_NAMES: [(&str, &[&str]); 15] = [
+  ("attack", &["Attack"]),
+  ("move", &["Move", "Patrol", "Hold Position"]),
+  ("stop", &["Stop"]),
+  ("ChronoBoostEnergyCost", &["Chrono Boost"]),
+  ("SpawnLarva", &["Inject Larva"]),
+  ("CalldownMULE", &["Calldown: MULE"]),
+  ("SupplyDrop", &["Calldown: Extra Supplies"]),
+  ("ScannerSweep", &["Scanner Sweep"]),
+  ("CommandCenterTrain", &["Train SCV"]),
+  ("NexusTrain", &["Train Probe"]),
+  ("TrainQueen", &["Train Queen"]),
+  ("BarracksTrain", &["Train Marine", "Train Reaper", "Train Ghost", "Train Marauder"]),
+  ("GatewayTrain", &[
+    "Train Zealot",
+    "Train Stalker",
+    "",
+    "Train High Templar",
+    "Train Dark Templar",
+    "Train Sentry",
+    "Train Adept",
+  ]),
+  ("WarpGateTrain", &[
+    "Warp In Zealot",
+    "Warp In Stalker",
+    "",
+    "Warp In High Templar",
+    "Warp In Dark Templar",
+    "Warp In Sentry",
+    "Warp In Adept",
+  ]),
+  ("LarvaTrain", &[
+    "Morph Drone",
+    "Morph Zergling",
+    "Morph Overlord",
+    "Morph Hydralisk",
+    "Morph Mutalisk",
+    "",
+    "Morph Ultralisk",
+    "",
+    "",
+    "Morph Roach",
+    "Morph Infestor",
+    "Morph Corruptor",
+    "Morph Viper",
+    "",
+    "Morph Swarm Host",
+  ]),
+];
+
+// ability links are rows in the game's ability catalog so they change between
+// patches. tables for other builds are supplied as "link,CatalogName" lines
+pub struct Abilities {
+  links: HashMap<u16, String>,
+}
+
+impl Default for Abilities {
+  fn default() -> Abilities {
+    Abilities::from_csv(EMBEDDED_ABILITIES).expect("Couldn't parse embedded abilities")
+  }
+}
+
+impl Abilities {
+  // only the embedded ability table
+  pub fn new() -> Abilities {
+    Abilities::default()
+  }
+
+  // blank lines and lines starting with # are ignored
+  pub fn from_csv(contents: &str) -> Result<Abilities, &'static str> {
+    let mut links = HashMap::new();
+    for line in contents.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (link, name) = match line.split_once(',') {
+        Some(entry) => entry,
+        None => return Err("Ability line is missing a name"),
+      };
+      let link = match link.trim().parse::<u16>() {
+        Ok(link) => link,
+        Err(_) => return Err("Ability link is not a number"),
+      };
+      links.insert(link, name.trim().to_string());
+    }
+
+    Ok(Abilities { links })
+  }
+
+  // whether commands of a catalog ability can be recognised, e.g. "SpawnLarva"
+  pub fn knows(&self, catalog_name: &str) -> bool {
+    self.links.values().any(|name| name == catalog_name)
+  }
+
+  // readable command name, falling back to the catalog name
+  pub fn name(&self, link: u16, command_index: u8) -> Option<String> {
+    let catalog_name = self.links.get(&link)?;
+
+    let command_name = COMMAND_NAMES
+      .iter()
+      .find(|(name, _)| name == catalog_name)
+      .and_then(|(_, commands)| commands.get(command_index as usize))
+      .filter(|command_name| !command_name.is_empty());
+
+    match command_name {
+      Some(command_name) => Some(command_name.to_string()),
+      None => Some(catalog_name.clone()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const TABLE: &str = "# link,CatalogName
+12,SpawnLarva
+40,GatewayTrain
+
+41,CalldownMULE
+";
+
+  #[test]
+  fn resolves_links_to_command_names() {
+    let abilities = Abilities::from_csv(TABLE).unwrap();
+    assert_eq!(abilities.name(12, 0).as_deref(), Some("Inject Larva"));
+    assert_eq!(abilities.name(40, 1).as_deref(), Some("Train Stalker"));
+    assert_eq!(abilities.name(41, 0).as_deref(), Some("Calldown: MULE"));
+  }
+
+  #[test]
+  fn falls_back_to_catalog_names() {
+    let abilities = Abilities::from_csv(TABLE).unwrap();
+    // unnamed command of a named ability
+    assert_eq!(abilities.name(40, 2).as_deref(), Some("GatewayTrain"));
+    assert_eq!(abilities.name(13, 0), None);
+  }
+
+  #[test]
+  fn knows_catalog_names() {
+    let abilities = Abilities::from_csv(TABLE).unwrap();
+    assert!(abilities.knows("SpawnLarva"));
+    assert!(!abilities.knows("ChronoBoostEnergyCost"));
+  }
+
+  #[test]
+  fn rejects_malformed_lines() {
+    assert!(Abilities::from_csv("SpawnLarva").is_err());
+    assert!(Abilities::from_csv("link,SpawnLarva").is_err());
+  }
+}
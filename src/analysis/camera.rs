@@ -0,0 +1,142 @@
+use crate::decoders::{find_field, find_value, DecoderResult};
+use crate::events::object_event::TOWN_HALLS;
+use crate::game::Game;
+use crate::replay::Event;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+// heatmaps split the map into GRID_SIZE x GRID_SIZE cells
+const GRID_SIZE: usize = 16;
+// camera targets are fixed point with 8 fractional bits
+const CAMERA_SCALE: f32 = 256.0;
+// cameras centred within this many map cells of a town hall are looking at that base
+const BASE_RADIUS: f32 = 15.0;
+
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct CameraUsage {
+  // (gameloop, x, y) in map cells
+  pub positions: Vec<(Gameloop, u16, u16)>,
+  // seconds the camera was centred in each grid cell, rows start at the bottom
+  // of the map. empty when the map size isn't known
+  pub heatmap: Vec<Vec<f32>>,
+  // seconds spent looking at bases of the player's team, opponents' bases and anywhere else
+  pub own_base: f32,
+  pub opponent_base: f32,
+  pub elsewhere: f32,
+}
+
+// (team, x, y, started, died) of every town hall
+type TownHall = (u8, f32, f32, Gameloop, Gameloop);
+
+// per player camera usage indexed by player id - 1, empty when SCameraUpdateEvent wasn't decoded
+pub fn player_camera_usage(
+  game_events: &[Event],
+  user_players: &HashMap<i64, u8>,
+  player_count: usize,
+  map_size: Option<(u16, u16)>,
+  game: &Game,
+  names: &[String],
+  game_end: Gameloop,
+) -> Vec<CameraUsage> {
+  let mut positions: Vec<Vec<(Gameloop, f32, f32)>> = vec![vec![]; player_count];
+  let mut decoded = false;
+
+  for event in game_events {
+    let mut is_camera = false;
+    let mut gameloop = Gameloop(0);
+    let mut userid = None;
+    for (field, value) in &event.entries {
+      match (field.as_str(), value) {
+        ("_event", DecoderResult::Name(name)) => is_camera = name == "NNet.Game.SCameraUpdateEvent",
+        ("_gameloop", DecoderResult::Value(value)) => gameloop = Gameloop(*value as u32),
+        ("_userid", DecoderResult::Value(value)) => userid = Some(*value),
+        _other => continue,
+      }
+    }
+
+    if !is_camera {
+      continue;
+    }
+    decoded = true;
+
+    let player_index = match userid.and_then(|userid| user_players.get(&userid)) {
+      Some(player_id) if (1..=player_count).contains(&(*player_id as usize)) => (*player_id - 1) as usize,
+      _other => continue,
+    };
+
+    // the target is missing when the camera didn't move, e.g. only the zoom changed
+    if let Some(DecoderResult::Struct(target)) = find_field(&event.entries, "m_target") {
+      let x = find_value(target, "x").unwrap_or(0) as f32 / CAMERA_SCALE;
+      let y = find_value(target, "y").unwrap_or(0) as f32 / CAMERA_SCALE;
+      positions[player_index].push((gameloop, x, y));
+    }
+  }
+
+  if !decoded {
+    return vec![];
+  }
+
+  let town_halls: Vec<TownHall> = game.objects
+    .iter()
+    .filter(|obj| TOWN_HALLS.contains(&names[obj.object_name_idx].as_str()))
+    .filter_map(|obj| {
+      let team = game.teams.get((obj.player_id as usize).wrapping_sub(1)).copied().flatten()?;
+      let (_, x, y) = obj.positions.first()?;
+      Some((team, *x as f32, *y as f32, obj.init_gameloop, obj.died_gameloop.unwrap_or(game_end)))
+    })
+    .collect();
+
+  positions
+    .iter()
+    .enumerate()
+    .map(|(player_index, player_positions)| {
+      let mut usage = CameraUsage {
+        positions: player_positions
+          .iter()
+          .map(|(gameloop, x, y)| (*gameloop, x.round() as u16, y.round() as u16))
+          .collect(),
+        ..Default::default()
+      };
+      let team = match game.teams[player_index] {
+        Some(team) => team,
+        None => return usage,
+      };
+
+      if map_size.is_some() {
+        usage.heatmap = vec![vec![0.0; GRID_SIZE]; GRID_SIZE];
+      }
+
+      // the camera stays where it is until the next update
+      for (position_index, (gameloop, x, y)) in player_positions.iter().enumerate() {
+        let until = match player_positions.get(position_index + 1) {
+          Some((next_gameloop, _, _)) => *next_gameloop,
+          None => game_end.max(*gameloop),
+        };
+        let seconds = until.saturating_sub(*gameloop).seconds();
+
+        if let Some((width, height)) = map_size {
+          let column = ((x / width.max(1) as f32) * GRID_SIZE as f32) as usize;
+          let row = ((y / height.max(1) as f32) * GRID_SIZE as f32) as usize;
+          usage.heatmap[row.min(GRID_SIZE - 1)][column.min(GRID_SIZE - 1)] += seconds;
+        }
+
+        let base_team = town_halls
+          .iter()
+          .filter(|(_, _, _, started, died)| started <= gameloop && gameloop < died)
+          .find(|(_, base_x, base_y, _, _)| ((x - base_x).powi(2) + (y - base_y).powi(2)).sqrt() <= BASE_RADIUS)
+          .map(|(base_team, _, _, _, _)| *base_team);
+
+        match base_team {
+          Some(base_team) if base_team == team => usage.own_base += seconds,
+          Some(_) => usage.opponent_base += seconds,
+          None => usage.elsewhere += seconds,
+        }
+      }
+
+      usage
+    })
+    .collect()
+}
@@ -0,0 +1,108 @@
+use crate::analysis::abilities::Abilities;
+use crate::decoders::{find_value, DecoderResult};
+use crate::replay::Event;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+const CMD_EVENT: &str = "NNet.Game.SCmdEvent";
+
+// m_cmdFlags bit set when shift queueing
+const QUEUED_FLAG: i64 = 0x2;
+// target points are fixed point with 12 fractional bits
+const POINT_SCALE: f32 = 4096.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Command {
+  pub gameloop: Gameloop,
+  pub ability_link: u16,
+  pub command_index: u8,
+  // None when the link isn't in the ability table for this build
+  pub ability: Option<String>,
+  pub target_point: Option<(f32, f32)>,
+  // unit tag, matches GameObject.tag_id
+  pub target_unit: Option<u32>,
+  pub queued: bool,
+}
+
+// commands indexed by player id - 1, empty when SCmdEvent wasn't decoded
+// so no commands can be told apart from commands that weren't decoded
+pub fn player_commands(
+  game_events: &[Event],
+  user_players: &HashMap<i64, u8>,
+  player_count: usize,
+  abilities: &Abilities,
+) -> Vec<Vec<Command>> {
+  let mut commands: Vec<Vec<Command>> = vec![vec![]; player_count];
+  let mut decoded = false;
+
+  for event in game_events {
+    let mut is_command = false;
+    let mut gameloop = Gameloop(0);
+    let mut userid = None;
+    let mut flags = 0;
+    let mut ability = None;
+    let mut target_point = None;
+    let mut target_unit = None;
+
+    for (field, value) in &event.entries {
+      match (field.as_str(), value) {
+        ("_event", DecoderResult::Name(name)) => {
+          is_command = name == CMD_EVENT;
+          decoded |= is_command;
+        },
+        ("_gameloop", DecoderResult::Value(value)) => gameloop = Gameloop(*value as u32),
+        ("_userid", DecoderResult::Value(value)) => userid = Some(*value),
+        ("m_cmdFlags", DecoderResult::Value(value)) => flags = *value,
+        ("m_abil", DecoderResult::Struct(abil)) => ability = match (
+          find_value(abil, "m_abilLink"),
+          find_value(abil, "m_abilCmdIndex"),
+        ) {
+          (Some(link), Some(command_index)) => Some((link as u16, command_index as u8)),
+          _other => None,
+        },
+        ("m_data", DecoderResult::Choice((choice, data))) => match (choice.as_str(), data.as_ref()) {
+          ("TargetPoint", DecoderResult::Struct(point)) => target_point = match (
+            find_value(point, "x"),
+            find_value(point, "y"),
+          ) {
+            (Some(x), Some(y)) => Some((x as f32 / POINT_SCALE, y as f32 / POINT_SCALE)),
+            _other => None,
+          },
+          ("TargetUnit", DecoderResult::Struct(unit)) =>
+            target_unit = find_value(unit, "m_tag").map(|tag| tag as u32),
+          _other => continue,
+        },
+        _other => continue,
+      }
+    }
+
+    // m_abil is optional, commands without one aren't logged
+    let (ability_link, command_index) = match ability {
+      Some(ability) if is_command => ability,
+      _other => continue,
+    };
+
+    let player_index = match userid.and_then(|userid| user_players.get(&userid)) {
+      Some(player_id) if (1..=player_count).contains(&(*player_id as usize)) => (*player_id - 1) as usize,
+      _other => continue,
+    };
+
+    commands[player_index].push(Command {
+      gameloop,
+      ability_link,
+      command_index,
+      ability: abilities.name(ability_link, command_index),
+      target_point,
+      target_unit,
+      queued: flags & QUEUED_FLAG != 0,
+    });
+  }
+
+  if !decoded {
+    return vec![];
+  }
+  commands
+}
@@ -0,0 +1,385 @@
+use crate::decoders::{find_field, find_value, DecoderResult};
+use crate::game::Game;
+use crate::replay::Event;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+const CONTROL_GROUP_COUNT: usize = 10;
+// m_controlGroupId of the active selection in selection deltas
+const ACTIVE_SELECTION: usize = 10;
+
+// m_controlGroupUpdate values
+const SET_GROUP: i64 = 0;
+const APPEND_GROUP: i64 = 1;
+const RECALL_GROUP: i64 = 2;
+const CLEAR_GROUP: i64 = 3;
+const SET_AND_STEAL_GROUP: i64 = 4;
+const APPEND_AND_STEAL_GROUP: i64 = 5;
+
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct ControlGroup {
+  // m_controlGroupIndex, 0-9
+  pub index: u8,
+  pub sets: u16,
+  pub appends: u16,
+  pub recalls: u16,
+  // the most units of each type the group held at once
+  pub unit_types: Vec<(String, u16)>,
+  // (gameloop, units by type) every time the group's units changed
+  pub changes: Vec<(Gameloop, Vec<(String, u16)>)>,
+}
+
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct ControlGroups {
+  pub groups_used: u8,
+  pub recalls: u16,
+  // only groups that were assigned units
+  pub groups: Vec<ControlGroup>,
+}
+
+// unit tags in each control group with the active selection last, kept in selection order
+struct PlayerSelection {
+  units: Vec<Vec<u32>>,
+  // tag -> (m_subgroupPriority, m_unitLink) from the selection deltas that added it
+  subgroups: HashMap<u32, (i64, i64)>,
+  groups: Vec<ControlGroup>,
+}
+
+// per player control groups indexed by player id - 1, empty when
+// SControlGroupUpdateEvent and SSelectionDeltaEvent weren't decoded
+pub fn player_control_groups(
+  game_events: &[Event],
+  user_players: &HashMap<i64, u8>,
+  player_count: usize,
+  game: &Game,
+  names: &[String],
+) -> Vec<ControlGroups> {
+  let mut players: Vec<PlayerSelection> = (0..player_count)
+    .map(|_| PlayerSelection {
+      units: vec![vec![]; CONTROL_GROUP_COUNT + 1],
+      subgroups: HashMap::new(),
+      groups: (0..CONTROL_GROUP_COUNT)
+        .map(|index| ControlGroup {
+          index: index as u8,
+          ..Default::default()
+        })
+        .collect(),
+    })
+    .collect();
+  let mut decoded = false;
+
+  for event in game_events {
+    let mut name = "";
+    let mut gameloop = Gameloop(0);
+    let mut userid = None;
+    for (field, value) in &event.entries {
+      match (field.as_str(), value) {
+        ("_event", DecoderResult::Name(value)) => name = value,
+        ("_gameloop", DecoderResult::Value(value)) => gameloop = Gameloop(*value as u32),
+        ("_userid", DecoderResult::Value(value)) => userid = Some(*value),
+        _other => continue,
+      }
+    }
+
+    if name != "NNet.Game.SSelectionDeltaEvent" && name != "NNet.Game.SControlGroupUpdateEvent" {
+      continue;
+    }
+    decoded = true;
+
+    let player_index = match userid.and_then(|userid| user_players.get(&userid)) {
+      Some(player_id) if (1..=player_count).contains(&(*player_id as usize)) => (*player_id - 1) as usize,
+      _other => continue,
+    };
+    let player = &mut players[player_index];
+
+    if name == "NNet.Game.SSelectionDeltaEvent" {
+      let group_id = match find_value(&event.entries, "m_controlGroupId") {
+        Some(group_id) if (group_id as usize) <= ACTIVE_SELECTION => group_id as usize,
+        _other => continue,
+      };
+      let delta = match find_field(&event.entries, "m_delta") {
+        Some(DecoderResult::Struct(delta)) => delta,
+        _other => continue,
+      };
+
+      let mut units = match find_field(delta, "m_removeMask") {
+        Some(mask) => remove_masked(&player.units[group_id], mask),
+        None => player.units[group_id].clone(),
+      };
+      if let Some(DecoderResult::Array(tags)) = find_field(delta, "m_addUnitTags") {
+        let tags: Vec<u32> = tags.iter().filter_map(fragment_value).collect();
+        // added tags are listed subgroup by subgroup, m_count tags each
+        if let Some(DecoderResult::Array(subgroups)) = find_field(delta, "m_addSubgroups") {
+          let subgroup_tags = subgroups
+            .iter()
+            .filter_map(|subgroup| match subgroup {
+              DecoderResult::Struct(subgroup) => Some((
+                find_value(subgroup, "m_subgroupPriority").unwrap_or(0),
+                find_value(subgroup, "m_unitLink").unwrap_or(0),
+                find_value(subgroup, "m_count").unwrap_or(0),
+              )),
+              _other => None,
+            })
+            .flat_map(|(priority, unit_link, count)| (0..count).map(move |_| (priority, unit_link)));
+          player.subgroups.extend(tags.iter().copied().zip(subgroup_tags));
+        }
+        units.extend(tags);
+      }
+      sort_selection(&mut units, &player.subgroups);
+
+      if group_id != ACTIVE_SELECTION && units != player.units[group_id] {
+        let group = &mut player.groups[group_id];
+        record_change(group, &units, gameloop, game, names);
+      }
+      player.units[group_id] = units;
+      continue;
+    }
+
+    let index = match find_value(&event.entries, "m_controlGroupIndex") {
+      Some(index) if (index as usize) < CONTROL_GROUP_COUNT => index as usize,
+      _other => continue,
+    };
+    let update = find_value(&event.entries, "m_controlGroupUpdate").unwrap_or(-1);
+
+    let selection = player.units[ACTIVE_SELECTION].clone();
+    let units = match update {
+      SET_GROUP | SET_AND_STEAL_GROUP => {
+        player.groups[index].sets += 1;
+        selection.clone()
+      },
+      APPEND_GROUP | APPEND_AND_STEAL_GROUP => {
+        player.groups[index].appends += 1;
+        let mut units = player.units[index].clone();
+        units.extend(selection.iter());
+        sort_selection(&mut units, &player.subgroups);
+        units
+      },
+      // dead units are masked out of the group when it's recalled
+      RECALL_GROUP => {
+        player.groups[index].recalls += 1;
+        let units = match find_field(&event.entries, "m_mask") {
+          Some(mask) => remove_masked(&player.units[index], mask),
+          None => player.units[index].clone(),
+        };
+        player.units[ACTIVE_SELECTION] = units.clone();
+        units
+      },
+      CLEAR_GROUP => vec![],
+      _other => continue,
+    };
+
+    // stealing takes the selected units out of every other group
+    if update == SET_AND_STEAL_GROUP || update == APPEND_AND_STEAL_GROUP {
+      for other_index in (0..CONTROL_GROUP_COUNT).filter(|other_index| *other_index != index) {
+        let remaining: Vec<u32> = player.units[other_index]
+          .iter()
+          .filter(|tag| !selection.contains(tag))
+          .copied()
+          .collect();
+        if remaining != player.units[other_index] {
+          record_change(&mut player.groups[other_index], &remaining, gameloop, game, names);
+          player.units[other_index] = remaining;
+        }
+      }
+    }
+
+    if units != player.units[index] {
+      record_change(&mut player.groups[index], &units, gameloop, game, names);
+      player.units[index] = units;
+    }
+  }
+
+  if !decoded {
+    return vec![];
+  }
+
+  players
+    .into_iter()
+    .map(|player| {
+      let groups: Vec<ControlGroup> = player.groups
+        .into_iter()
+        .filter(|group| !group.changes.is_empty())
+        .collect();
+
+      ControlGroups {
+        groups_used: groups.len() as u8,
+        recalls: groups.iter().map(|group| group.recalls).sum(),
+        groups,
+      }
+    })
+    .collect()
+}
+
+fn record_change(group: &mut ControlGroup, units: &[u32], gameloop: Gameloop, game: &Game, names: &[String]) {
+  let mut unit_types: Vec<(String, u16)> = vec![];
+  for tag in units {
+    // tags are shared with tracker events, so types come from the tracked objects
+    let name = match game.object(*tag) {
+      Some(obj) => obj.name_at(names, gameloop),
+      None => continue,
+    };
+    match unit_types.iter_mut().find(|(unit_type, _)| unit_type == name) {
+      Some((_, count)) => *count += 1,
+      None => unit_types.push((name.to_string(), 1)),
+    }
+  }
+
+  for (unit_type, count) in &unit_types {
+    match group.unit_types.iter_mut().find(|(group_type, _)| group_type == unit_type) {
+      Some((_, most)) => *most = (*most).max(*count),
+      None => group.unit_types.push((unit_type.clone(), *count)),
+    }
+  }
+
+  group.changes.push((gameloop, unit_types));
+}
+
+// the game orders selections by subgroup, highest priority first with unit types
+// of equal priority kept together. units within a subgroup are ordered by tag,
+// which matches the game for units selected together but can differ for units
+// added to a subgroup later
+fn sort_selection(units: &mut Vec<u32>, subgroups: &HashMap<u32, (i64, i64)>) {
+  units.sort_by_key(|tag| {
+    let (priority, unit_link) = subgroups.get(tag).copied().unwrap_or_default();
+    (Reverse(priority), Reverse(unit_link), *tag)
+  });
+  units.dedup();
+}
+
+// masks index into the units in selection order
+fn remove_masked(units: &[u32], mask: &DecoderResult) -> Vec<u32> {
+  let (kind, data) = match mask {
+    DecoderResult::Choice((kind, data)) => (kind.as_str(), data.as_ref()),
+    _other => return units.to_vec(),
+  };
+
+  let indices: Vec<usize> = match data {
+    DecoderResult::Array(indices) => indices
+      .iter()
+      .filter_map(fragment_value)
+      .map(|index| index as usize)
+      .collect(),
+    _other => vec![],
+  };
+
+  units
+    .iter()
+    .enumerate()
+    .filter(|(index, _)| match (kind, data) {
+      ("Mask", DecoderResult::Bits(bits)) => !bits.get(*index).copied().unwrap_or(false),
+      ("OneIndices", _) => !indices.contains(index),
+      ("ZeroIndices", _) => indices.contains(index),
+      _other => true,
+    })
+    .map(|(_, tag)| *tag)
+    .collect()
+}
+
+// integer arrays are decoded as data fragments
+fn fragment_value(value: &DecoderResult) -> Option<u32> {
+  match value {
+    DecoderResult::DataFragment(value) => Some(*value),
+    DecoderResult::Value(value) => Some(*value as u32),
+    _other => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::events::object_event::ObjectType;
+  use crate::game::GameObject;
+
+  const MARINE_LINK: i64 = 48;
+  const MEDIVAC_LINK: i64 = 54;
+
+  fn names() -> Vec<String> {
+    vec!["Marine".to_string(), "Medivac".to_string()]
+  }
+
+  fn unit(tag_id: u32, name_idx: usize) -> GameObject {
+    GameObject {
+      object_name_idx: name_idx,
+      object_type: ObjectType::Unit,
+      tag_id,
+      tag_index: tag_id,
+      player_id: 1,
+      owners: vec![(Gameloop(0), 1)],
+      init_gameloop: Gameloop(0),
+      done_gameloop: Some(Gameloop(0)),
+      died_gameloop: None,
+      killer_player_id: None,
+      creator_tag_id: None,
+      types: vec![(Gameloop(0), name_idx)],
+      positions: vec![],
+      supply: 1.0,
+      supply_provided: 0.0,
+    }
+  }
+
+  fn event(name: &str, gameloop: u32, mut entries: Vec<(String, DecoderResult)>) -> Event {
+    let mut event = vec![
+      ("_event".to_string(), DecoderResult::Name(name.to_string())),
+      ("_gameloop".to_string(), DecoderResult::Value(gameloop as i64)),
+      ("_userid".to_string(), DecoderResult::Value(0)),
+    ];
+    event.append(&mut entries);
+    Event::new(event)
+  }
+
+  // (priority, unit link, count) subgroups and the tags they add
+  fn selection_delta(
+    gameloop: u32,
+    remove_mask: (&str, DecoderResult),
+    subgroups: &[(i64, i64, i64)],
+    tags: &[u32],
+  ) -> Event {
+    let subgroups = subgroups
+      .iter()
+      .map(|(priority, unit_link, count)| DecoderResult::Struct(vec![
+        ("m_unitLink".to_string(), DecoderResult::Value(*unit_link)),
+        ("m_subgroupPriority".to_string(), DecoderResult::Value(*priority)),
+        ("m_intraSubgroupPriority".to_string(), DecoderResult::Value(0)),
+        ("m_count".to_string(), DecoderResult::Value(*count)),
+      ]))
+      .collect();
+    let tags = tags.iter().map(|tag| DecoderResult::DataFragment(*tag)).collect();
+
+    event("NNet.Game.SSelectionDeltaEvent", gameloop, vec![
+      ("m_controlGroupId".to_string(), DecoderResult::Value(ACTIVE_SELECTION as i64)),
+      ("m_delta".to_string(), DecoderResult::Struct(vec![
+        ("m_subgroupIndex".to_string(), DecoderResult::Value(0)),
+        ("m_removeMask".to_string(), DecoderResult::Choice((remove_mask.0.to_string(), Box::new(remove_mask.1)))),
+        ("m_addSubgroups".to_string(), DecoderResult::Array(subgroups)),
+        ("m_addUnitTags".to_string(), DecoderResult::Array(tags)),
+      ])),
+    ])
+  }
+
+  #[test]
+  fn masks_units_in_subgroup_order() {
+    let mut game = Game::new();
+    game.reset(vec![Some(1), Some(2)]);
+    game.objects = vec![unit(100, 0), unit(200, 0), unit(300, 1)];
+
+    let game_events = [
+      // the medivac has the highest tag but is first in the selection
+      selection_delta(10, ("None", DecoderResult::Null), &[(20, MEDIVAC_LINK, 1), (10, MARINE_LINK, 2)], &[300, 100, 200]),
+      selection_delta(20, ("OneIndices", DecoderResult::Array(vec![DecoderResult::DataFragment(0)])), &[], &[]),
+      event("NNet.Game.SControlGroupUpdateEvent", 30, vec![
+        ("m_controlGroupIndex".to_string(), DecoderResult::Value(1)),
+        ("m_controlGroupUpdate".to_string(), DecoderResult::Value(SET_GROUP)),
+      ]),
+    ];
+    let user_players = HashMap::from([(0, 1), (1, 2)]);
+
+    let control_groups = player_control_groups(&game_events, &user_players, 2, &game, &names());
+    assert_eq!(control_groups[0].groups.len(), 1);
+    assert_eq!(control_groups[0].groups[0].index, 1);
+    assert_eq!(control_groups[0].groups[0].unit_types, vec![("Marine".to_string(), 2)]);
+  }
+}
@@ -0,0 +1,299 @@
+use crate::decoders::{find_value, DecoderResult};
+use crate::game::Game;
+use crate::events::object_event::ObjectType;
+use crate::protocol::{GAME_USER_JOIN_EVENT, GAME_USER_LEAVE_EVENT, RECONNECT_NOTIFY_MESSAGE};
+use crate::replay::Event;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+pub const ENDING_EVENTS: [&str; 3] = [
+  GAME_USER_LEAVE_EVENT,
+  GAME_USER_JOIN_EVENT,
+  RECONNECT_NOTIFY_MESSAGE,
+];
+
+// m_leaveReason when the connection dropped, 0 is leaving normally
+const LEAVE_REASON_DROPPED: i64 = 1;
+
+// leaving this soon after the last building dies is part of being eliminated
+const ELIMINATION_LEAVE_GAMELOOPS: Gameloop = Gameloop::from_seconds(10);
+// reconnecting this soon before leaving means the player had connection problems
+const RECONNECT_WINDOW: Gameloop = Gameloop::from_seconds(60);
+
+// structures that keep a player in the game but aren't ObjectType::Building
+// since they're left out of builds
+const ELIMINATION_STRUCTURES: [&str; 10] = [
+  "Pylon",
+  "ShieldBattery",
+  "SupplyDepot",
+  "Bunker",
+  "MissileTurret",
+  "SensorTower",
+  "SpineCrawler",
+  "SporeCrawler",
+  "SpineCrawlerUprooted",
+  "SporeCrawlerUprooted",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum GameEnding {
+  // a player left the game, usually after typing gg
+  GgLeave,
+  // a player dropped or had connection problems before leaving
+  Disconnect,
+  // a team lost every building
+  Elimination,
+  // the replay ended without anyone leaving or being eliminated
+  TimeOut,
+  // leave, join and reconnect events weren't decoded
+  Unknown,
+}
+
+impl GameEnding {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      GameEnding::GgLeave => "gg_leave",
+      GameEnding::Disconnect => "disconnect",
+      GameEnding::Elimination => "elimination",
+      GameEnding::TimeOut => "time_out",
+      GameEnding::Unknown => "unknown",
+    }
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerConnection {
+  pub player_id: u8,
+  pub gameloop: Gameloop,
+  // m_leaveReason for leaves, m_status for reconnect messages
+  pub status: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Ending {
+  pub ending: GameEnding,
+  pub first_leaver: Option<u8>,
+  pub first_leave_gameloop: Option<Gameloop>,
+  pub leaves: Vec<PlayerConnection>,
+  pub joins: Vec<PlayerConnection>,
+  pub reconnects: Vec<PlayerConnection>,
+}
+
+// observers aren't in user_players so their leaves and joins are skipped
+pub fn game_ending(
+  game_events: &[Event],
+  message_events: &[Event],
+  user_players: &HashMap<i64, u8>,
+  game: &Game,
+  names: &[String],
+) -> Ending {
+  let mut leaves: Vec<PlayerConnection> = vec![];
+  let mut joins: Vec<PlayerConnection> = vec![];
+  let mut reconnects: Vec<PlayerConnection> = vec![];
+  let mut decoded = false;
+
+  for event in game_events.iter().chain(message_events.iter()) {
+    let mut name = "";
+    let mut gameloop = Gameloop(0);
+    let mut userid = None;
+    for (field, value) in &event.entries {
+      match (field.as_str(), value) {
+        ("_event", DecoderResult::Name(value)) => name = value,
+        ("_gameloop", DecoderResult::Value(value)) => gameloop = Gameloop(*value as u32),
+        ("_userid", DecoderResult::Value(value)) => userid = Some(*value),
+        _other => continue,
+      }
+    }
+
+    if name != GAME_USER_LEAVE_EVENT && name != GAME_USER_JOIN_EVENT && name != RECONNECT_NOTIFY_MESSAGE {
+      continue;
+    }
+    decoded = true;
+
+    let player_id = match userid.and_then(|userid| user_players.get(&userid)) {
+      Some(player_id) if game.teams.get((*player_id as usize).wrapping_sub(1)).copied().flatten().is_some() => *player_id,
+      _other => continue,
+    };
+
+    match name {
+      GAME_USER_LEAVE_EVENT => leaves.push(PlayerConnection {
+        player_id,
+        gameloop,
+        status: find_value(&event.entries, "m_leaveReason"),
+      }),
+      GAME_USER_JOIN_EVENT => joins.push(PlayerConnection {
+        player_id,
+        gameloop,
+        status: None,
+      }),
+      RECONNECT_NOTIFY_MESSAGE => reconnects.push(PlayerConnection {
+        player_id,
+        gameloop,
+        status: find_value(&event.entries, "m_status"),
+      }),
+      _other => continue,
+    }
+  }
+
+  leaves.sort_by_key(|leave| leave.gameloop);
+  reconnects.sort_by_key(|reconnect| reconnect.gameloop);
+
+  // the replay ends with the recording player leaving, so their leave is always
+  // last and only comes first when nobody else left. a lone leave on the final
+  // gameloop is kept since it's usually the recorder typing gg
+  let first_leave = leaves.first();
+
+  let ending = match (decoded, first_leave, eliminated_at(game, names)) {
+    (false, _, _) => GameEnding::Unknown,
+    (_, None, Some(_)) => GameEnding::Elimination,
+    (_, Some(leave), Some(eliminated)) if eliminated <= leave.gameloop + ELIMINATION_LEAVE_GAMELOOPS =>
+      GameEnding::Elimination,
+    (_, Some(leave), _) => {
+      let reconnected = reconnects
+        .iter()
+        .any(|reconnect|
+          reconnect.player_id == leave.player_id &&
+          reconnect.gameloop <= leave.gameloop &&
+          leave.gameloop - reconnect.gameloop <= RECONNECT_WINDOW
+        );
+      match leave.status {
+        Some(LEAVE_REASON_DROPPED) => GameEnding::Disconnect,
+        _other if reconnected => GameEnding::Disconnect,
+        _other => GameEnding::GgLeave,
+      }
+    },
+    (_, None, None) => GameEnding::TimeOut,
+  };
+
+  Ending {
+    ending,
+    first_leaver: first_leave.map(|leave| leave.player_id),
+    first_leave_gameloop: first_leave.map(|leave| leave.gameloop),
+    leaves,
+    joins,
+    reconnects,
+  }
+}
+
+// when the first team lost its last structure, if any team did. approximate
+// since creep tumors, flying buildings and structures of players who left
+// don't follow the game's elimination rules exactly
+fn eliminated_at(game: &Game, names: &[String]) -> Option<Gameloop> {
+  let mut eliminations: Vec<Gameloop> = vec![];
+
+  for team in game.teams.iter().flatten() {
+    let buildings: Vec<Option<Gameloop>> = game.objects
+      .iter()
+      .filter(|obj|
+        (
+          obj.object_type == ObjectType::Building ||
+          ELIMINATION_STRUCTURES.contains(&names[obj.object_name_idx].as_str())
+        ) &&
+        game.teams.get((obj.player_id as usize).wrapping_sub(1)).copied().flatten() == Some(*team)
+      )
+      .map(|obj| obj.died_gameloop)
+      .collect();
+
+    if !buildings.is_empty() && buildings.iter().all(|died| died.is_some()) {
+      eliminations.push(buildings.iter().flatten().copied().max().unwrap_or_default());
+    }
+  }
+
+  eliminations.into_iter().min()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::game::GameObject;
+
+  const GAME_END: Gameloop = Gameloop(20000);
+
+  fn names() -> Vec<String> {
+    vec!["Nexus".to_string(), "Pylon".to_string()]
+  }
+
+  fn structure(tag_id: u32, player_id: u8, name_idx: usize, died_gameloop: Option<Gameloop>) -> GameObject {
+    GameObject {
+      object_name_idx: name_idx,
+      object_type: if name_idx == 0 { ObjectType::Building } else { ObjectType::Other },
+      tag_id,
+      tag_index: tag_id,
+      player_id,
+      owners: vec![(Gameloop(0), player_id)],
+      init_gameloop: Gameloop(0),
+      done_gameloop: Some(Gameloop(0)),
+      died_gameloop,
+      killer_player_id: None,
+      creator_tag_id: None,
+      types: vec![(Gameloop(0), name_idx)],
+      positions: vec![],
+      supply: 0.0,
+      supply_provided: 0.0,
+    }
+  }
+
+  fn game(objects: Vec<GameObject>) -> Game {
+    let mut game = Game::new();
+    game.reset(vec![Some(1), Some(2)]);
+    game.objects = objects;
+    game
+  }
+
+  fn leave_event(gameloop: u32, userid: i64) -> Event {
+    Event::new(vec![
+      ("_event".to_string(), DecoderResult::Name(GAME_USER_LEAVE_EVENT.to_string())),
+      ("_gameloop".to_string(), DecoderResult::Value(gameloop as i64)),
+      ("_userid".to_string(), DecoderResult::Value(userid)),
+      ("m_leaveReason".to_string(), DecoderResult::Value(0)),
+    ])
+  }
+
+  fn user_players() -> HashMap<i64, u8> {
+    HashMap::from([(0, 1), (1, 2)])
+  }
+
+  fn nexuses() -> Vec<GameObject> {
+    vec![structure(1, 1, 0, None), structure(2, 2, 0, None)]
+  }
+
+  #[test]
+  fn gg_leave_on_the_final_gameloop() {
+    // the loser leaves, ending the game, then the recorder leaves on the same gameloop
+    let game_events = [leave_event(GAME_END.0, 1), leave_event(GAME_END.0, 0)];
+    let ending = game_ending(&game_events, &[], &user_players(), &game(nexuses()), &names());
+
+    assert_eq!(ending.ending, GameEnding::GgLeave);
+    assert_eq!(ending.first_leaver, Some(2));
+    assert_eq!(ending.first_leave_gameloop, Some(GAME_END));
+  }
+
+  #[test]
+  fn ignores_the_recorder_leaving_after_the_game() {
+    let game_events = [leave_event(15000, 1), leave_event(GAME_END.0, 0)];
+    let ending = game_ending(&game_events, &[], &user_players(), &game(nexuses()), &names());
+
+    assert_eq!(ending.ending, GameEnding::GgLeave);
+    assert_eq!(ending.first_leaver, Some(2));
+  }
+
+  #[test]
+  fn pylons_keep_a_player_alive() {
+    // player 2's nexus dies but their pylon is still alive or dies later
+    let objects = |pylon_died| vec![
+      structure(1, 1, 0, None),
+      structure(2, 2, 0, Some(Gameloop(10000))),
+      structure(3, 2, 1, pylon_died),
+    ];
+    let game_events = [leave_event(GAME_END.0, 0)];
+
+    let ending = game_ending(&game_events, &[], &user_players(), &game(objects(None)), &names());
+    assert_eq!(ending.ending, GameEnding::GgLeave);
+
+    let ending = game_ending(&game_events, &[], &user_players(), &game(objects(Some(Gameloop(12000)))), &names());
+    assert_eq!(ending.ending, GameEnding::Elimination);
+  }
+}
@@ -0,0 +1,378 @@
+use crate::analysis::abilities::Abilities;
+use crate::analysis::commands::Command;
+use crate::events::object_event::{production_time, transition_time};
+use crate::game::Game;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+// injects keep a hatchery spawning larva for 29s and queue up
+const INJECT_GAMELOOPS: Gameloop = Gameloop(650);
+
+const ORBITAL_START_ENERGY: f32 = 50.0;
+// 0.7875 energy per second
+const ENERGY_PER_GAMELOOP: f32 = 0.03515625;
+const CALLDOWN_ENERGY: f32 = 50.0;
+
+const HATCHERIES: [&str; 3] = [
+  "Hatchery",
+  "Lair",
+  "Hive",
+];
+
+// buildings that can train units in their current form. warpgates and
+// flying buildings can't, so that time isn't counted as idle
+const PRODUCTION_BUILDINGS: [&str; 10] = [
+  "Nexus",
+  "Gateway",
+  "RoboticsFacility",
+  "Stargate",
+  "CommandCenter",
+  "OrbitalCommand",
+  "PlanetaryFortress",
+  "Barracks",
+  "Factory",
+  "Starport",
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InjectUptime {
+  pub name: String,
+  pub x: u16,
+  pub y: u16,
+  pub injects: u16,
+  // fraction of the hatchery's completed lifetime spent injected
+  pub uptime: f32,
+}
+
+// inject, chrono, scan and supply drop numbers come from commands, so they're
+// None unless SCmdEvent is decoded with an ability table that names them
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct MacroMetrics {
+  pub injects: Option<u16>,
+  pub inject_uptime: Vec<InjectUptime>,
+  // mean uptime across hatcheries
+  pub average_inject_uptime: Option<f32>,
+  pub chrono_boosts: Option<u16>,
+  // (building name, chrono boosts) with the most boosted first
+  pub chrono_targets: Vec<(String, u16)>,
+  // counted from MULE units so they don't need commands
+  pub mules: u16,
+  pub scans: Option<u16>,
+  pub supply_drops: Option<u16>,
+  // energy spent on calldowns out of the energy orbitals generated
+  pub energy_efficiency: Option<f32>,
+  // seconds production buildings could train units but weren't
+  pub idle_production: u16,
+  pub idle_production_ratio: f32,
+}
+
+// per player metrics indexed by player id - 1
+pub fn player_macro_metrics(
+  game: &Game,
+  names: &[String],
+  commands: &[Vec<Command>],
+  abilities: &Abilities,
+  game_end: Gameloop,
+) -> Vec<MacroMetrics> {
+  let player_count = game.teams.len();
+  let mut metrics = vec![MacroMetrics::default(); player_count];
+
+  // commands are empty when SCmdEvent wasn't decoded
+  let counts = |catalog_name: &str| (!commands.is_empty() && abilities.knows(catalog_name)).then_some(0);
+  let injects_known = counts("SpawnLarva");
+  let chrono_boosts_known = counts("ChronoBoostEnergyCost");
+  let scans_known = counts("ScannerSweep");
+  let supply_drops_known = counts("SupplyDrop");
+
+  // (start, end) of every unit trained by each production building
+  let mut production: HashMap<u32, Vec<(Gameloop, Gameloop)>> = HashMap::new();
+  for obj in &game.objects {
+    let creator_tag_id = match obj.creator_tag_id {
+      Some(creator_tag_id) => creator_tag_id,
+      None => continue,
+    };
+    if let Some(gameloops) = production_time(obj.name_at(names, obj.init_gameloop)) {
+      let born = obj.init_gameloop;
+      production
+        .entry(creator_tag_id)
+        .or_default()
+        .push((born.saturating_sub(gameloops), born));
+    }
+  }
+
+  for (player_index, player_metrics) in metrics.iter_mut().enumerate() {
+    if game.teams[player_index].is_none() {
+      continue;
+    }
+    let player_id = (player_index + 1) as u8;
+    let player_commands = match commands.get(player_index) {
+      Some(player_commands) => player_commands.as_slice(),
+      None => &[],
+    };
+    player_metrics.injects = injects_known;
+    player_metrics.chrono_boosts = chrono_boosts_known;
+    player_metrics.scans = scans_known;
+    player_metrics.supply_drops = supply_drops_known;
+
+    // target tag -> gameloops the ability was used on it
+    let mut injects: HashMap<u32, Vec<Gameloop>> = HashMap::new();
+    let mut chrono_targets: HashMap<String, u16> = HashMap::new();
+    for command in player_commands {
+      let count = match (command.ability.as_deref(), command.target_unit) {
+        (Some("Inject Larva"), Some(target)) => {
+          injects.entry(target).or_default().push(command.gameloop);
+          &mut player_metrics.injects
+        },
+        (Some("Chrono Boost"), Some(target)) => {
+          if let Some(obj) = game.object(target) {
+            *chrono_targets.entry(obj.name_at(names, command.gameloop).to_string()).or_default() += 1;
+          }
+          &mut player_metrics.chrono_boosts
+        },
+        (Some("Scanner Sweep"), _) => &mut player_metrics.scans,
+        (Some("Calldown: Extra Supplies"), _) => &mut player_metrics.supply_drops,
+        _other => continue,
+      };
+      if let Some(count) = count {
+        *count += 1;
+      }
+    }
+
+    let mut chrono_targets: Vec<(String, u16)> = chrono_targets.into_iter().collect();
+    chrono_targets.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    player_metrics.chrono_targets = chrono_targets;
+
+    let mut energy_generated = 0.0;
+    let mut available_gameloops = Gameloop::default();
+    let mut idle_gameloops = Gameloop::default();
+    for obj in game.objects.iter().filter(|obj| obj.player_id == player_id) {
+      let name = &names[obj.object_name_idx];
+      let end = obj.died_gameloop.unwrap_or(game_end);
+
+      if name == "MULE" {
+        player_metrics.mules += 1;
+      }
+
+      let done = match obj.done_gameloop {
+        Some(done) if done < end => done,
+        _other => continue,
+      };
+
+      if HATCHERIES.contains(&name.as_str()) && injects_known.is_some() {
+        let mut hatchery_injects = injects.remove(&obj.tag_id).unwrap_or_default();
+        hatchery_injects.sort();
+
+        let mut injected_until = Gameloop::default();
+        let mut injected = Gameloop::default();
+        for inject in &hatchery_injects {
+          let start = (*inject).max(injected_until).max(done);
+          injected_until = start + INJECT_GAMELOOPS;
+          injected = injected + injected_until.min(end).saturating_sub(start);
+        }
+
+        let (x, y) = obj.positions.first().map_or((0, 0), |(_, x, y)| (*x, *y));
+        player_metrics.inject_uptime.push(InjectUptime {
+          name: name.clone(),
+          x,
+          y,
+          injects: hatchery_injects.len() as u16,
+          uptime: injected.0 as f32 / (end - done).0 as f32,
+        });
+      }
+
+      if let Some((orbital_gameloop, _)) = obj.types.iter().find(|(_, name_idx)| names[*name_idx] == "OrbitalCommand") {
+        let orbital_start = (*orbital_gameloop).max(done);
+        energy_generated += ORBITAL_START_ENERGY +
+          ENERGY_PER_GAMELOOP * end.saturating_sub(orbital_start).0 as f32;
+      }
+
+      // time in a form that can train units, minus the time spent training or morphing
+      let mut busy = production.get(&obj.tag_id).cloned().unwrap_or_default();
+      for (type_gameloop, type_name_idx) in &obj.types {
+        if let Some(gameloops) = transition_time(&names[*type_name_idx]) {
+          busy.push((type_gameloop.saturating_sub(gameloops), *type_gameloop));
+        }
+      }
+      let busy = merge_intervals(busy);
+
+      for (type_index, (type_gameloop, type_name_idx)) in obj.types.iter().enumerate() {
+        if !PRODUCTION_BUILDINGS.contains(&names[*type_name_idx].as_str()) {
+          continue;
+        }
+
+        let start = (*type_gameloop).max(done);
+        let stop = match obj.types.get(type_index + 1) {
+          Some((next_gameloop, _)) => (*next_gameloop).min(end),
+          None => end,
+        };
+        if stop <= start {
+          continue;
+        }
+
+        let busy_gameloops = busy
+          .iter()
+          .fold(Gameloop::default(), |total, (busy_start, busy_end)| {
+            total + (*busy_end).min(stop).saturating_sub((*busy_start).max(start))
+          });
+        available_gameloops = available_gameloops + (stop - start);
+        idle_gameloops = idle_gameloops + (stop - start).saturating_sub(busy_gameloops);
+      }
+    }
+
+    if !player_metrics.inject_uptime.is_empty() {
+      player_metrics.average_inject_uptime = Some(player_metrics.inject_uptime
+        .iter()
+        .map(|hatchery| hatchery.uptime)
+        .sum::<f32>() / player_metrics.inject_uptime.len() as f32);
+    }
+
+    if let (Some(scans), Some(supply_drops)) = (player_metrics.scans, player_metrics.supply_drops) {
+      if energy_generated > 0.0 {
+        let calldowns = player_metrics.mules + scans + supply_drops;
+        player_metrics.energy_efficiency = Some((calldowns as f32 * CALLDOWN_ENERGY / energy_generated).min(1.0));
+      }
+    }
+
+    if available_gameloops > Gameloop::default() {
+      player_metrics.idle_production = idle_gameloops.whole_seconds();
+      player_metrics.idle_production_ratio = idle_gameloops.0 as f32 / available_gameloops.0 as f32;
+    }
+  }
+
+  metrics
+}
+
+// sorted, non overlapping intervals
+fn merge_intervals(mut intervals: Vec<(Gameloop, Gameloop)>) -> Vec<(Gameloop, Gameloop)> {
+  intervals.sort();
+
+  let mut merged: Vec<(Gameloop, Gameloop)> = vec![];
+  for (start, end) in intervals {
+    match merged.last_mut() {
+      Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+      _other => merged.push((start, end)),
+    }
+  }
+
+  merged
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::analysis::commands::player_commands;
+  use crate::decoders::DecoderResult;
+  use crate::events::object_event::ObjectType;
+  use crate::game::GameObject;
+  use crate::replay::Event;
+
+  const HATCHERY_TAG: u32 = 0x40001;
+  const TABLE: &str = "12,SpawnLarva";
+
+  fn names() -> Vec<String> {
+    vec!["Hatchery".to_string()]
+  }
+
+  // hatchery finished at 1000 and alive until the game ends
+  fn game() -> Game {
+    let mut game = Game::new();
+    game.reset(vec![Some(1), Some(2)]);
+    game.objects.push(GameObject {
+      object_name_idx: 0,
+      object_type: ObjectType::Building,
+      tag_id: HATCHERY_TAG,
+      tag_index: 1,
+      player_id: 1,
+      owners: vec![(Gameloop(0), 1)],
+      init_gameloop: Gameloop(0),
+      done_gameloop: Some(Gameloop(1000)),
+      died_gameloop: None,
+      killer_player_id: None,
+      creator_tag_id: None,
+      types: vec![(Gameloop(0), 0)],
+      positions: vec![(Gameloop(0), 30, 40)],
+      supply: 0.0,
+      supply_provided: 6.0,
+    });
+    game
+  }
+
+  // SCmdEvent targeting a unit, shaped like the decoder's output
+  fn target_unit_command(gameloop: u32, userid: i64, link: i64, tag: u32) -> Event {
+    let value = |value: i64| DecoderResult::Value(value);
+    Event::new(vec![
+      ("_event".to_string(), DecoderResult::Name("NNet.Game.SCmdEvent".to_string())),
+      ("_gameloop".to_string(), value(gameloop as i64)),
+      ("_userid".to_string(), value(userid)),
+      ("m_cmdFlags".to_string(), value(0)),
+      ("m_abil".to_string(), DecoderResult::Struct(vec![
+        ("m_abilLink".to_string(), value(link)),
+        ("m_abilCmdIndex".to_string(), value(0)),
+        ("m_abilCmdData".to_string(), DecoderResult::Null),
+      ])),
+      ("m_data".to_string(), DecoderResult::Choice((
+        "TargetUnit".to_string(),
+        Box::new(DecoderResult::Struct(vec![
+          ("m_targetUnitFlags".to_string(), value(0)),
+          ("m_timer".to_string(), value(0)),
+          ("m_tag".to_string(), value(tag as i64)),
+          ("m_snapshotUnitLink".to_string(), value(100)),
+          ("m_snapshotControlPlayerId".to_string(), value(1)),
+          ("m_snapshotUpkeepPlayerId".to_string(), value(1)),
+          ("m_snapshotPoint".to_string(), DecoderResult::Struct(vec![
+            ("x".to_string(), value(30 * 4096)),
+            ("y".to_string(), value(40 * 4096)),
+            ("z".to_string(), value(0)),
+          ])),
+        ])),
+      ))),
+      ("m_sequence".to_string(), value(1)),
+      ("m_otherUnit".to_string(), DecoderResult::Null),
+      ("m_unitGroup".to_string(), DecoderResult::Null),
+    ])
+  }
+
+  fn metrics(abilities: &Abilities, game_events: &[Event]) -> Vec<MacroMetrics> {
+    let user_players = HashMap::from([(0, 1), (1, 2)]);
+    let commands = player_commands(game_events, &user_players, 2, abilities);
+    player_macro_metrics(&game(), &names(), &commands, abilities, Gameloop(2300))
+  }
+
+  #[test]
+  fn counts_injects_from_commands() {
+    let abilities = Abilities::from_csv(TABLE).unwrap();
+    let metrics = metrics(&abilities, &[target_unit_command(900, 0, 12, HATCHERY_TAG)]);
+
+    assert_eq!(metrics[0].injects, Some(1));
+    assert_eq!(metrics[0].inject_uptime.len(), 1);
+    assert_eq!(metrics[0].inject_uptime[0].injects, 1);
+    // injected from completion for 650 of the 1300 remaining gameloops
+    assert_eq!(metrics[0].average_inject_uptime, Some(0.5));
+    assert_eq!(metrics[1].injects, Some(0));
+    // the table doesn't name chrono boosts or calldowns
+    assert_eq!(metrics[0].chrono_boosts, None);
+    assert_eq!(metrics[0].energy_efficiency, None);
+  }
+
+  #[test]
+  fn leaves_command_metrics_empty_without_ability_names() {
+    let abilities = Abilities::from_csv("").unwrap();
+    let metrics = metrics(&abilities, &[target_unit_command(900, 0, 12, HATCHERY_TAG)]);
+
+    assert_eq!(metrics[0].injects, None);
+    assert_eq!(metrics[0].average_inject_uptime, None);
+    assert!(metrics[0].inject_uptime.is_empty());
+  }
+
+  #[test]
+  fn leaves_command_metrics_empty_without_commands() {
+    let abilities = Abilities::from_csv(TABLE).unwrap();
+    let metrics = metrics(&abilities, &[]);
+
+    assert_eq!(metrics[0].injects, None);
+    assert_eq!(metrics[0].average_inject_uptime, None);
+  }
+}
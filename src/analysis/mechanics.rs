@@ -0,0 +1,189 @@
+use crate::decoders::{find_field, find_value, DecoderResult};
+use crate::replay::Event;
+use crate::time::Gameloop;
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+// game events needed for apm, epm and spm
+pub const MECHANICS_EVENTS: [&str; 6] = [
+  "NNet.Game.SCmdEvent",
+  "NNet.Game.SSelectionDeltaEvent",
+  "NNet.Game.SControlGroupUpdateEvent",
+  "NNet.Game.SCmdUpdateTargetPointEvent",
+  "NNet.Game.SCmdUpdateTargetUnitEvent",
+  "NNet.Game.SCameraUpdateEvent",
+];
+
+// the opening worker split is mostly spam
+const EXCLUDED_START: Gameloop = Gameloop::from_seconds(10);
+const WINDOW: Gameloop = Gameloop::from_seconds(60);
+
+// repeating the same action this quickly doesn't do anything new
+const REPEAT_GAMELOOPS: Gameloop = Gameloop(19);
+// selections this close together are drag boxes and double clicks being refined
+const RESELECT_GAMELOOPS: Gameloop = Gameloop(6);
+
+// camera moves shorter than this are scrolling rather than a new screen
+const SCREEN_DISTANCE: f32 = 15.0;
+// camera targets are fixed point with 8 fractional bits
+const CAMERA_SCALE: f32 = 256.0;
+
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct Mechanics {
+  pub apm: u16,
+  pub epm: u16,
+  pub spm: u16,
+  // (window start in seconds, apm, epm, spm)
+  pub timeline: Vec<(u16, u16, u16, u16)>,
+}
+
+#[derive(Default, Clone)]
+struct PlayerActions {
+  // (gameloop, effective)
+  actions: Vec<(Gameloop, bool)>,
+  screens: Vec<Gameloop>,
+  previous_action: Option<(Gameloop, &'static str, i64, i64)>,
+  previous_selection: Option<Gameloop>,
+  camera: Option<(f32, f32)>,
+}
+
+// per player mechanics indexed by player id - 1, empty when no game events were decoded
+pub fn player_mechanics(
+  game_events: &[Event],
+  user_players: &HashMap<i64, u8>,
+  player_count: usize,
+  game_length: Gameloop,
+) -> Vec<Mechanics> {
+  let mut players = vec![PlayerActions::default(); player_count];
+  let mut decoded = false;
+
+  for event in game_events {
+    let mut name = "";
+    let mut gameloop = Gameloop::default();
+    let mut userid = None;
+    for (field, value) in &event.entries {
+      match (field.as_str(), value) {
+        ("_event", DecoderResult::Name(value)) => name = value,
+        ("_gameloop", DecoderResult::Value(value)) => gameloop = Gameloop(*value as u32),
+        ("_userid", DecoderResult::Value(value)) => userid = Some(*value),
+        _other => continue,
+      }
+    }
+
+    let player_index = match userid.and_then(|userid| user_players.get(&userid)) {
+      Some(player_id) if (1..=player_count).contains(&(*player_id as usize)) => (*player_id - 1) as usize,
+      _other => continue,
+    };
+    let player = &mut players[player_index];
+
+    // key identifies repeats of the same action
+    let key = match name {
+      "NNet.Game.SCmdEvent" => match find_field(&event.entries, "m_abil") {
+        Some(DecoderResult::Struct(abil)) => (
+          "cmd",
+          find_value(abil, "m_abilLink").unwrap_or(-1),
+          find_value(abil, "m_abilCmdIndex").unwrap_or(-1),
+        ),
+        _other => ("cmd", -1, -1),
+      },
+      "NNet.Game.SControlGroupUpdateEvent" => (
+        "group",
+        find_value(&event.entries, "m_controlGroupIndex").unwrap_or(-1),
+        find_value(&event.entries, "m_controlGroupUpdate").unwrap_or(-1),
+      ),
+      "NNet.Game.SSelectionDeltaEvent" => ("select", 0, 0),
+      "NNet.Game.SCmdUpdateTargetPointEvent" |
+      "NNet.Game.SCmdUpdateTargetUnitEvent" => ("target", 0, 0),
+      "NNet.Game.SCameraUpdateEvent" => {
+        decoded = true;
+        if let Some(DecoderResult::Struct(target)) = find_field(&event.entries, "m_target") {
+          let x = find_value(target, "x").unwrap_or(0) as f32 / CAMERA_SCALE;
+          let y = find_value(target, "y").unwrap_or(0) as f32 / CAMERA_SCALE;
+          let moved = match player.camera {
+            Some((previous_x, previous_y)) =>
+              ((x - previous_x).powi(2) + (y - previous_y).powi(2)).sqrt() >= SCREEN_DISTANCE,
+            None => false,
+          };
+          if moved {
+            player.screens.push(gameloop);
+          }
+          player.camera = Some((x, y));
+        }
+        continue;
+      },
+      _other => continue,
+    };
+    decoded = true;
+
+    let repeated = match player.previous_action {
+      Some((previous_gameloop, previous_kind, previous_a, previous_b)) =>
+        (previous_kind, previous_a, previous_b) == key &&
+        gameloop - previous_gameloop < REPEAT_GAMELOOPS,
+      None => false,
+    };
+    let reselected = key.0 == "select" && match player.previous_selection {
+      Some(previous_gameloop) => gameloop - previous_gameloop < RESELECT_GAMELOOPS,
+      None => false,
+    };
+
+    player.actions.push((gameloop, !repeated && !reselected));
+    player.previous_action = Some((gameloop, key.0, key.1, key.2));
+    if key.0 == "select" {
+      player.previous_selection = Some(gameloop);
+    }
+  }
+
+  if !decoded {
+    return vec![];
+  }
+
+  players
+    .iter()
+    .map(|player| {
+      let actions: Vec<Gameloop> = player.actions.iter().map(|(gameloop, _)| *gameloop).collect();
+      let effective_actions: Vec<Gameloop> = player.actions
+        .iter()
+        .filter(|(_, effective)| *effective)
+        .map(|(gameloop, _)| *gameloop)
+        .collect();
+
+      let mut timeline = vec![];
+      let mut window_start = Gameloop::default();
+      while window_start < game_length {
+        let window_end = (window_start + WINDOW).min(game_length);
+        timeline.push((
+          window_start.whole_seconds(),
+          per_minute(&actions, window_start, window_end),
+          per_minute(&effective_actions, window_start, window_end),
+          per_minute(&player.screens, window_start, window_end),
+        ));
+        window_start = window_start + WINDOW;
+      }
+
+      Mechanics {
+        apm: per_minute(&actions, Gameloop::default(), game_length),
+        epm: per_minute(&effective_actions, Gameloop::default(), game_length),
+        spm: per_minute(&player.screens, Gameloop::default(), game_length),
+        timeline,
+      }
+    })
+    .collect()
+}
+
+// rate of gameloops between start and end, ignoring the excluded start of the game
+fn per_minute(gameloops: &[Gameloop], start: Gameloop, end: Gameloop) -> u16 {
+  let start = start.max(EXCLUDED_START);
+  if end <= start {
+    return 0;
+  }
+
+  let count = gameloops
+    .iter()
+    .filter(|gameloop| **gameloop >= start && **gameloop < end)
+    .count();
+  let minutes = (end - start).seconds() / 60.0;
+
+  (count as f32 / minutes).round() as u16
+}
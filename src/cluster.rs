@@ -1,41 +1,8 @@
 use std::cmp::min;
 use std::mem::swap;
-use std::time::Instant;
 
 use serde::Serialize;
 
-#[derive(Serialize, Clone)]
-pub struct ClusterTreeNode {
-  pub label: String,
-  pub matchup: String,
-  pub children: Vec<ClusterTreeNode>,
-}
-
-#[derive(Serialize, Clone)]
-pub struct ClusterTree {
-  pub terms: Vec<ClusterTreeNode>,
-}
-
-#[derive(Serialize, Clone)]
-pub struct Cluster {
-  pub build: ClusterBuild,
-  pub matchup: String,
-  pub total: u16,
-  pub wins: u16,
-  pub losses: u16,
-  pub cluster: Vec<ClusterBuild>,
-  pub tree: RadixTrie,
-}
-
-#[derive(Serialize, Clone)]
-pub struct ClusterBuild {
-  pub build: String,
-  pub total: u16,
-  pub wins: u16,
-  pub losses: u16,
-  pub diff: f32,
-}
-
 #[derive(Serialize, Clone, Debug)]
 pub struct BuildCount {
   pub total: u16,
@@ -121,8 +88,8 @@ impl Node {
     let mut inserted = false;
     for child in &mut self.children {
       if child.label == build_fragment {
-        child.value.add(&count);
-        self.value.add(&count);
+        child.value.add(count);
+        self.value.add(count);
 
         inserted = true;
         break;
@@ -137,14 +104,14 @@ impl Node {
       if compare_fragment == child.label {
         let next_fragment = &build_fragment[child.label.len() + 1..];
 
-        if child.children.len() != 0 {
-          child.walk(&next_fragment, count);
+        if !child.children.is_empty() {
+          child.walk(next_fragment, count);
         } else {
           let new_node = Node::new(next_fragment.to_string(), count.clone());
           child.children.push(new_node);
-          child.value.add(&count);
+          child.value.add(count);
         }
-        self.value.add(&count);
+        self.value.add(count);
 
         inserted = true;
         break;
@@ -152,14 +119,14 @@ impl Node {
 
       if child.label.starts_with(compare_fragment) {
         child.split_at(compare_fragment.len());
-        child.value.add(&count);
-        self.value.add(&count);
+        child.value.add(count);
+        self.value.add(count);
 
         inserted = true;
         break;
       }
 
-      let match_length = child.match_key(&build_fragment);
+      let match_length = child.match_key(build_fragment);
       if match_length == 0 {
         continue;
       }
@@ -170,8 +137,8 @@ impl Node {
         let remaining_fragment = build_fragment[match_length + 1..].to_string();
         let new_node = Node::new(remaining_fragment, count.clone());
         child.children.push(new_node);
-        child.value.add(&count);
-        self.value.add(&count);
+        child.value.add(count);
+        self.value.add(count);
 
         inserted = true;
         break;
@@ -185,7 +152,7 @@ impl Node {
     if !inserted {
       let new_node = Node::new(build_fragment.to_string(), count.clone());
       self.children.push(new_node);
-      self.value.add(&count);
+      self.value.add(count);
     }
   }
 }
@@ -40,10 +40,6 @@ impl BitPackedBuffer {
   //   self.used >= self.data_len
   // }
 
-  fn used_bits(&self) -> usize {
-    (self.used * 8) - self.nextbits
-  }
-
   fn byte_align(&mut self) {
     self.nextbits = 0;
   }
@@ -53,7 +49,7 @@ impl BitPackedBuffer {
     let data = &self.data[self.used..self.used + bytes];
     self.used += bytes;
 
-    if data.len() != bytes as usize {
+    if data.len() != bytes {
       panic!("TruncatedError");
     }
 
@@ -93,7 +89,7 @@ impl BitPackedBuffer {
       let shifted_next: u8 = (self.next as u16 >> copybits) as u8;
       self.next = shifted_next;
       self.nextbits -= copybits as usize;
-      resultbits += copybits as u8;
+      resultbits += copybits;
     }
 
     result
@@ -109,43 +105,23 @@ impl BitPackedBuffer {
   }
 }
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum EventField {
-  Gameloop,
-  ControlPlayerId,
-  PlayerId,
-  UnitTypeName,
-  UnitTagIndex,
-  UnitTagRecycle,
-  Stats,
-}
+pub type EventEntry =  (String, DecoderResult);
 
-#[derive(Debug, PartialEq, Clone)]
-pub enum StatsField {
-  WorkersActiveCount,
-  MineralsCollectionRate,
-  VespeneCollectionRate,
-  MineralsCurrent,
-  VespeneCurrent,
-  MineralsLostArmy,
-  MineralsLostEconomy,
-  MineralsLostTechnology,
-  VespeneLostArmy,
-  VespeneLostEconomy,
-  VespeneLostTechnology,
-  MineralsUsedInProgressArmy,
-  MineralsUsedCurrentArmy,
-  VespeneUsedInProgressArmy,
-  VespeneUsedCurrentArmy,
+pub fn find_field<'a>(entries: &'a [EventEntry], name: &str) -> Option<&'a DecoderResult> {
+  entries
+    .iter()
+    .find(|(field, _)| field == name)
+    .map(|(_, value)| value)
 }
 
-pub enum EventType {
-  ObjectEvent,
-  PlayerStatsEvent,
+// finds an integer field in decoded struct entries
+pub fn find_value(entries: &[EventEntry], name: &str) -> Option<i64> {
+  match entries.iter().find(|(field, _)| field == name) {
+    Some((_, DecoderResult::Value(value))) => Some(*value),
+    _other => None,
+  }
 }
 
-pub type EventEntry =  (String, DecoderResult);
-
 #[derive(Clone, Debug)]
 pub enum DecoderResult {
   Name(String),
@@ -153,17 +129,20 @@ pub enum DecoderResult {
   Blob(String),
   Array(Vec<DecoderResult>),
   DataFragment(u32),
-  Pair((i64, i16)),
   Gameloop((String, i64)),
   Bool(bool),
   Struct(Vec<EventEntry>),
+  // choices of anything other than a single value, e.g. a target point struct
+  Choice((String, Box<DecoderResult>)),
+  // element i is bit i of the bitarray, counting from the least significant bit
+  Bits(Vec<bool>),
   Null,
   Empty,
 }
 
 pub trait Decoder {
-  fn instance<'a>(
-    &'a mut self,
+  fn instance(
+    &mut self,
     typeinfos: &[ProtocolTypeInfo],
     typeid: &u8,
     event_allowed: bool,
@@ -199,10 +178,6 @@ pub trait Decoder {
     buffer.used >= buffer.data_len
   }
 
-  fn used_bits(buffer: &BitPackedBuffer) -> usize {
-    buffer.used_bits()
-  }
-
   fn _int(&mut self, bounds: &Int) -> DecoderResult;
 
   fn _blob(&mut self, bounds: &Int) -> DecoderResult;
@@ -220,11 +195,11 @@ pub trait Decoder {
   fn _choice(
     &mut self,
     bounds: &Int,
-    fields: &Vec<(i64, (&str, u8))>,
+    fields: &[(i64, (&str, u8))],
     event_allowed: bool
   ) -> DecoderResult;
 
-  fn _struct<'a>(&'a mut self, fields: &[Struct], event_allowed: bool) -> DecoderResult;
+  fn _struct(&mut self, fields: &[Struct], event_allowed: bool) -> DecoderResult;
 }
 
 impl<'a> BitPackedDecoder<'a> {
@@ -269,8 +244,7 @@ impl Decoder for BitPackedDecoder<'_> {
         for _i in 0..value {
           let data = match self.instance(self.typeinfos, typeid, event_allowed) {
             DecoderResult::Value(value) => DecoderResult::DataFragment(value as u32),
-            DecoderResult::Struct(values) => DecoderResult::Struct(values),
-            _other => panic!("instance returned DecoderResult::{:?}", _other),
+            other => other,
           };
           array.push(data);
         }
@@ -284,9 +258,20 @@ impl Decoder for BitPackedDecoder<'_> {
   fn _bitarray(&mut self, bounds: &Int) -> DecoderResult {
     match self._int(bounds) {
       DecoderResult::Value(value) => {
-        let bytes = self.buffer.read_bits(value as u8);
-        // DecoderResult::Pair((value, bytes as i16))
-        DecoderResult::Pair((0, 0))
+        // read_bits can't read more than 128 bits at once, masks can be up to 512 bits.
+        // the most significant bits are read first
+        let mut remaining_bits = value as usize;
+        let mut bits = Vec::with_capacity(remaining_bits);
+        while remaining_bits > 0 {
+          let chunk_size = remaining_bits.min(64);
+          let chunk = self.buffer.read_bits(chunk_size as u8);
+          for bit in (0..chunk_size).rev() {
+            bits.push((chunk >> bit) & 1 == 1);
+          }
+          remaining_bits -= chunk_size;
+        }
+        bits.reverse();
+        DecoderResult::Bits(bits)
       }
       _other => panic!("instance didn't return DecoderResult::Value {:?}", _other),
     }
@@ -312,7 +297,7 @@ impl Decoder for BitPackedDecoder<'_> {
   fn _choice(
     &mut self,
     bounds: &Int,
-    fields: &Vec<(i64, (&str, u8))>,
+    fields: &[(i64, (&str, u8))],
     event_allowed: bool,
   ) -> DecoderResult {
     let tag = match self._int(bounds) {
@@ -322,14 +307,12 @@ impl Decoder for BitPackedDecoder<'_> {
 
     match fields.iter().find(|(field_tag, _)| *field_tag == tag) {
       Some((_, field)) => {
-        let choice_res = match self.instance(self.typeinfos, &field.1, event_allowed) {
-          DecoderResult::Value(value) => value,
-          _other => panic!("didn't find DecoderResult::Value"),
-        };
+        let choice_res = self.instance(self.typeinfos, &field.1, event_allowed);
         // println!("_choice instance returned {:?} {:?}", field.0, choice_res);
-        match event_allowed {
-          true => DecoderResult::Gameloop((field.0.to_owned(), choice_res)),
-          false => DecoderResult::Empty,
+        match (event_allowed, choice_res) {
+          (false, _) => DecoderResult::Empty,
+          (true, DecoderResult::Value(value)) => DecoderResult::Gameloop((field.0.to_owned(), value)),
+          (true, other) => DecoderResult::Choice((field.0.to_owned(), Box::new(other))),
         }
       },
       None => panic!("CorruptedError"),
@@ -421,7 +404,7 @@ impl<'a> VersionedDecoder<'a> {
       self.buffer.read_aligned_bytes(length as usize);
     } else if skip == 3 {
       // choice
-      let tag = self._vint();
+      self._vint();
       self._skip_instance();
     } else if skip == 4 {
       // optional
@@ -433,7 +416,7 @@ impl<'a> VersionedDecoder<'a> {
       // struct
       let length = self._vint();
       for _ in 0..length {
-        let tag = self._vint();
+        self._vint();
         self._skip_instance();
       }
     } else if skip == 6 {
@@ -453,12 +436,12 @@ impl<'a> VersionedDecoder<'a> {
 }
 
 impl Decoder for VersionedDecoder<'_> {
-  fn _int(&mut self, bounds: &Int) -> DecoderResult {
+  fn _int(&mut self, _bounds: &Int) -> DecoderResult {
     self.expect_skip(9);
     DecoderResult::Value(self._vint())
   }
 
-  fn _blob(&mut self, bounds: &Int) -> DecoderResult {
+  fn _blob(&mut self, _bounds: &Int) -> DecoderResult {
     self.expect_skip(2);
     let length = self._vint();
     DecoderResult::Blob(
@@ -473,7 +456,7 @@ impl Decoder for VersionedDecoder<'_> {
     DecoderResult::Bool(self.buffer.read_bits(8) != 0)
   }
 
-  fn _array(&mut self, bounds: &Int, typeid: &u8, event_allowed: bool) -> DecoderResult {
+  fn _array(&mut self, _bounds: &Int, typeid: &u8, event_allowed: bool) -> DecoderResult {
     self.expect_skip(0);
     let length = self._vint();
 
@@ -491,16 +474,12 @@ impl Decoder for VersionedDecoder<'_> {
     DecoderResult::Array(array)
   }
 
-  fn _bitarray(&mut self, bounds: &Int) -> DecoderResult {
+  fn _bitarray(&mut self, _bounds: &Int) -> DecoderResult {
     self.expect_skip(1);
+    // no versioned structure has a bitarray that's used, so it's skipped
     let length = self._vint();
-    let bytes = self.buffer.read_aligned_bytes((length as usize + 7) / 8);
-    let mut value: i16 = 0;
-    for v in bytes {
-      value += *v as i16;
-    }
-    // DecoderResult::Pair((length, value))
-    DecoderResult::Pair((0, 0))
+    self.buffer.read_aligned_bytes((length as usize).div_ceil(8));
+    DecoderResult::Null
   }
 
   fn _optional(&mut self, typeid: &u8, event_allowed: bool) -> DecoderResult {
@@ -523,8 +502,8 @@ impl Decoder for VersionedDecoder<'_> {
 
   fn _choice(
     &mut self,
-    bounds: &Int,
-    fields: &Vec<(i64, (&str, u8))>,
+    _bounds: &Int,
+    fields: &[(i64, (&str, u8))],
     event_allowed: bool
   ) -> DecoderResult {
     self.expect_skip(3);
@@ -544,7 +523,7 @@ impl Decoder for VersionedDecoder<'_> {
       },
       None => {
         self._skip_instance();
-        DecoderResult::Pair((0, 0))
+        DecoderResult::Null
       },
     }
   }
@@ -1,3 +1,6 @@
 
 pub mod player_stats_event;
 pub mod object_event;
+pub mod upgrade_event;
+pub mod positions_event;
+pub mod owner_change_event;
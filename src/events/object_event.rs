@@ -1,9 +1,9 @@
-use crate::game::{Game, GameObject};
+use crate::game::{BuildEntry, Game, GameObject, Resources, WorkerDeath};
 use crate::parser::TimelineContext;
 use crate::replay::Event;
 use crate::decoders::DecoderResult;
+use crate::time::Gameloop;
 
-use std::collections::hash_map::Entry;
 use std::collections::HashSet;
 
 const UNITS: [&str; 47] = [
@@ -53,7 +53,7 @@ const UNITS: [&str; 47] = [
   "Hydralisk",
   "LurkerMP",
   "Mutalisk",
-  "Corrupter",
+  "Corruptor",
   "SwarmHostMP",
   "Infestor",
   "Viper",
@@ -74,7 +74,7 @@ const BUILDINGS: [&str; 45] = [
   "TwilightCouncil",
   "RoboticsBay",
   "FleetBeacon",
-  "TemplarArchives",
+  "TemplarArchive",
   "DarkShrine",
 
   // Terran
@@ -147,6 +147,313 @@ const TRANSITION_BUILD_TIMES: [(&str, u16); 9] = [
   ("Corruptor", 538),
 ];
 
+pub const TOWN_HALLS: [&str; 7] = [
+  "Nexus",
+  "CommandCenter",
+  "OrbitalCommand",
+  "PlanetaryFortress",
+  "Hatchery",
+  "Lair",
+  "Hive",
+];
+
+const SUPPLY_COSTS: [(&str, f32); 51] = [
+  // Protoss
+  ("Probe", 1.0),
+  ("Zealot", 2.0),
+  ("Stalker", 2.0),
+  ("Sentry", 2.0),
+  ("Adept", 2.0),
+  ("HighTemplar", 2.0),
+  ("DarkTemplar", 2.0),
+  ("Archon", 4.0),
+  ("Observer", 1.0),
+  ("WarpPrism", 2.0),
+  ("Immortal", 4.0),
+  ("Colossus", 6.0),
+  ("Disruptor", 3.0),
+  ("Phoenix", 2.0),
+  ("VoidRay", 4.0),
+  ("Oracle", 3.0),
+  ("Tempest", 5.0),
+  ("Carrier", 6.0),
+  ("Mothership", 8.0),
+
+  // Terran
+  ("SCV", 1.0),
+  ("Marine", 1.0),
+  ("Reaper", 1.0),
+  ("Marauder", 2.0),
+  ("Ghost", 2.0),
+  ("Hellion", 2.0),
+  ("HellionTank", 2.0),
+  ("WidowMine", 2.0),
+  ("Cyclone", 3.0),
+  ("SiegeTank", 3.0),
+  ("Thor", 6.0),
+  ("VikingFighter", 2.0),
+  ("Medivac", 2.0),
+  ("Liberator", 3.0),
+  ("Raven", 2.0),
+  ("Banshee", 3.0),
+  ("Battlecruiser", 6.0),
+
+  // Zerg
+  ("Drone", 1.0),
+  ("Queen", 2.0),
+  ("Zergling", 0.5),
+  ("Baneling", 0.5),
+  ("Roach", 2.0),
+  ("Ravager", 3.0),
+  ("Hydralisk", 2.0),
+  ("LurkerMP", 3.0),
+  ("Mutalisk", 2.0),
+  ("Corruptor", 2.0),
+  ("SwarmHostMP", 3.0),
+  ("Infestor", 2.0),
+  ("Viper", 3.0),
+  ("Ultralisk", 6.0),
+  ("BroodLord", 4.0),
+];
+
+// cocoons use the supply of the unit they morph into
+const SUPPLY_COCOONS: [(&str, f32); 4] = [
+  ("BanelingCocoon", 0.5),
+  ("RavagerCocoon", 3.0),
+  ("LurkerMPEgg", 3.0),
+  ("BroodLordCocoon", 4.0),
+];
+
+const SUPPLY_PROVIDED: [(&str, f32); 14] = [
+  ("Pylon", 8.0),
+  ("Nexus", 15.0),
+  ("SupplyDepot", 8.0),
+  ("CommandCenter", 15.0),
+  ("OrbitalCommand", 15.0),
+  ("PlanetaryFortress", 15.0),
+  ("Overlord", 8.0),
+  ("OverlordCocoon", 8.0),
+  ("Overseer", 8.0),
+  ("OverlordTransport", 8.0),
+  ("OverseerSiegeMode", 8.0),
+  ("Hatchery", 6.0),
+  ("Lair", 6.0),
+  ("Hive", 6.0),
+];
+
+// None for objects that aren't in the table, which includes
+// alternate modes like burrowed or sieged units that keep their supply
+fn supply_cost(name: &str) -> Option<f32> {
+  SUPPLY_COSTS
+    .iter()
+    .chain(SUPPLY_COCOONS.iter())
+    .find(|(supply_name, _)| *supply_name == name)
+    .map(|(_, supply)| *supply)
+}
+
+fn supply_provided(name: &str) -> Option<f32> {
+  SUPPLY_PROVIDED
+    .iter()
+    .find(|(supply_name, _)| *supply_name == name)
+    .map(|(_, supply)| *supply)
+}
+
+// total (minerals, gas) spent on an object, including what it morphed from
+const OBJECT_COSTS: [(&str, i64, i64); 106] = [
+  // Protoss
+  ("Probe", 50, 0),
+  ("Zealot", 100, 0),
+  ("Stalker", 125, 50),
+  ("Sentry", 50, 100),
+  ("Adept", 100, 25),
+  ("HighTemplar", 50, 150),
+  ("DarkTemplar", 125, 125),
+  ("Archon", 175, 275),
+  ("Observer", 25, 75),
+  ("WarpPrism", 250, 0),
+  ("Immortal", 275, 100),
+  ("Colossus", 300, 200),
+  ("Disruptor", 150, 150),
+  ("Phoenix", 150, 100),
+  ("VoidRay", 250, 150),
+  ("Oracle", 150, 150),
+  ("Tempest", 250, 175),
+  ("Carrier", 350, 250),
+  ("Mothership", 400, 400),
+  ("Nexus", 400, 0),
+  ("Pylon", 100, 0),
+  ("Assimilator", 75, 0),
+  ("Gateway", 150, 0),
+  ("WarpGate", 150, 0),
+  ("Forge", 150, 0),
+  ("CyberneticsCore", 150, 0),
+  ("PhotonCannon", 150, 0),
+  ("ShieldBattery", 100, 0),
+  ("RoboticsFacility", 150, 100),
+  ("Stargate", 150, 150),
+  ("TwilightCouncil", 150, 100),
+  ("RoboticsBay", 150, 150),
+  ("FleetBeacon", 300, 200),
+  ("TemplarArchive", 150, 200),
+  ("DarkShrine", 150, 150),
+
+  // Terran
+  ("SCV", 50, 0),
+  ("Marine", 50, 0),
+  ("Reaper", 50, 50),
+  ("Marauder", 100, 25),
+  ("Ghost", 150, 125),
+  ("Hellion", 100, 0),
+  ("HellionTank", 100, 0),
+  ("WidowMine", 75, 25),
+  ("Cyclone", 150, 100),
+  ("SiegeTank", 150, 125),
+  ("Thor", 300, 200),
+  ("VikingFighter", 150, 75),
+  ("Medivac", 100, 100),
+  ("Liberator", 150, 125),
+  ("Raven", 100, 150),
+  ("Banshee", 150, 100),
+  ("Battlecruiser", 400, 300),
+  ("CommandCenter", 400, 0),
+  ("OrbitalCommand", 550, 0),
+  ("PlanetaryFortress", 550, 150),
+  ("SupplyDepot", 100, 0),
+  ("Refinery", 75, 0),
+  ("Barracks", 150, 0),
+  ("EngineeringBay", 125, 0),
+  ("Bunker", 100, 0),
+  ("MissileTurret", 100, 0),
+  ("GhostAcademy", 150, 50),
+  ("Factory", 150, 100),
+  ("Starport", 150, 100),
+  ("Armory", 150, 100),
+  ("FusionCore", 150, 150),
+  ("BarracksTechLab", 50, 25),
+  ("FactoryTechLab", 50, 25),
+  ("StarportTechLab", 50, 25),
+  ("BarracksReactor", 50, 50),
+  ("FactoryReactor", 50, 50),
+  ("StarportReactor", 50, 50),
+
+  // Zerg
+  ("Drone", 50, 0),
+  ("Overlord", 100, 0),
+  ("Overseer", 150, 50),
+  ("Queen", 150, 0),
+  ("Zergling", 25, 0),
+  ("Baneling", 50, 25),
+  ("Roach", 75, 25),
+  ("Ravager", 100, 100),
+  ("Hydralisk", 100, 50),
+  ("LurkerMP", 150, 150),
+  ("Mutalisk", 100, 100),
+  ("Corruptor", 150, 100),
+  ("BroodLord", 300, 250),
+  ("SwarmHostMP", 100, 75),
+  ("Infestor", 100, 150),
+  ("Viper", 100, 200),
+  ("Ultralisk", 275, 200),
+  ("Hatchery", 300, 0),
+  ("Lair", 450, 100),
+  ("Hive", 650, 250),
+  ("Extractor", 25, 0),
+  ("SpawningPool", 200, 0),
+  ("EvolutionChamber", 75, 0),
+  ("SpineCrawler", 100, 0),
+  ("SporeCrawler", 75, 0),
+  ("RoachWarren", 150, 0),
+  ("BanelingNest", 100, 50),
+  ("HydraliskDen", 100, 100),
+  ("LurkerDenMP", 100, 150),
+  ("Spire", 200, 200),
+  ("GreaterSpire", 300, 350),
+  ("NydusNetwork", 150, 150),
+  ("InfestationPit", 100, 100),
+  ("UltraliskCavern", 150, 200),
+];
+
+pub fn object_cost(name: &str) -> Option<Resources> {
+  OBJECT_COSTS
+    .iter()
+    .find(|(cost_name, _, _)| *cost_name == name)
+    .map(|(_, minerals, gas)| Resources {
+      minerals: *minerals,
+      gas: *gas,
+    })
+}
+
+// gameloops to train units from production buildings, larva or hatcheries.
+// used to work out when a building was busy from the units it produced and
+// when trained units started using supply
+const PRODUCTION_TIMES: [(&str, u16); 46] = [
+  // Protoss
+  ("Probe", 269),
+  ("Zealot", 605),
+  ("Stalker", 605),
+  ("Sentry", 515),
+  ("Adept", 672),
+  ("HighTemplar", 874),
+  ("DarkTemplar", 874),
+  ("Observer", 470),
+  ("WarpPrism", 806),
+  ("Immortal", 874),
+  ("Colossus", 1210),
+  ("Disruptor", 806),
+  ("Phoenix", 560),
+  ("VoidRay", 829),
+  ("Oracle", 829),
+  ("Tempest", 963),
+  ("Carrier", 1434),
+  ("Mothership", 1770),
+
+  // Terran
+  ("SCV", 269),
+  ("Marine", 403),
+  ("Reaper", 717),
+  ("Marauder", 470),
+  ("Ghost", 650),
+  ("Hellion", 470),
+  ("HellionTank", 470),
+  ("WidowMine", 470),
+  ("Cyclone", 717),
+  ("SiegeTank", 717),
+  ("Thor", 963),
+  ("VikingFighter", 672),
+  ("Medivac", 672),
+  ("Liberator", 963),
+  ("Raven", 762),
+  ("Banshee", 963),
+  ("Battlecruiser", 1434),
+
+  // Zerg
+  ("Drone", 269),
+  ("Queen", 806),
+  ("Zergling", 381),
+  ("Roach", 426),
+  ("Hydralisk", 538),
+  ("Mutalisk", 538),
+  ("Corruptor", 650),
+  ("SwarmHostMP", 650),
+  ("Infestor", 806),
+  ("Viper", 650),
+  ("Ultralisk", 874),
+];
+
+pub fn production_time(name: &str) -> Option<Gameloop> {
+  PRODUCTION_TIMES
+    .iter()
+    .find(|(production_name, _)| *production_name == name)
+    .map(|(_, gameloops)| Gameloop::from(*gameloops))
+}
+
+pub fn transition_time(name: &str) -> Option<Gameloop> {
+  TRANSITION_BUILD_TIMES
+    .iter()
+    .find(|(transition_name, _)| *transition_name == name)
+    .map(|(_, gameloops)| Gameloop::from(*gameloops))
+}
+
 const WORKERS: [&str; 3] = [
   "SCV",
   "Probe",
@@ -159,14 +466,18 @@ pub struct ObjectEvent;
 pub enum ObjectType {
   Building,
   Unit,
+  Other,
 }
 
 const MAX_BUILD_LENGTH: u8 = 15;
 const MAX_UNIT_BUILD_LENGTH: u8 = 30;
 const MAX_UNIT_TYPES: u8 = 10;
 
+// only the first 7min of the game are part of a build
+const BUILD_WINDOW: Gameloop = Gameloop::from_seconds(420);
+
 impl ObjectEvent {
-  pub fn new(
+  pub fn handle(
     names: &mut Vec<String>,
     context: &mut TimelineContext,
     game: &mut Game,
@@ -175,10 +486,17 @@ impl ObjectEvent {
   ) -> Result<(), &'static str> {
     let mut player_id: u8 = 0;
     let mut event_object_name = "";
-    let mut event_object_type = ObjectType::Building;
+    let mut event_object_type = ObjectType::Other;
     let mut tag_index = 0;
     let mut tag_recycle = 0;
-    let mut current_gameloop = 0;
+    let mut current_gameloop = Gameloop(0);
+    let mut event_x: Option<u16> = None;
+    let mut event_y: Option<u16> = None;
+    let mut killer_player_id: Option<u8> = None;
+    let mut killer_tag_index: Option<u32> = None;
+    let mut killer_tag_recycle: Option<u32> = None;
+    let mut creator_tag_index: Option<u32> = None;
+    let mut creator_tag_recycle: Option<u32> = None;
 
     for (field, value) in &event.entries {
       match field.as_str() {
@@ -188,17 +506,33 @@ impl ObjectEvent {
           return Err("Player ID is not a value");
         },
         "m_unitTypeName" => if let DecoderResult::Blob(name) = value {
+          event_object_name = name;
+
           if BUILDINGS.contains(&name.as_str()) {
-            event_object_name = name;
-            // event_object_type = "building";
+            event_object_type = ObjectType::Building;
           }
 
-          // if UNITS.contains(&name.as_str()) {
-          //   event_object_name = name;
-          //   event_object_type = "unit";
-          // }
+          if UNITS.contains(&name.as_str()) || WORKERS.contains(&name.as_str()) {
+            event_object_type = ObjectType::Unit;
+          }
+        },
+        // killer fields are optional and decode to Null when missing
+        "m_killerPlayerId" => if let DecoderResult::Value(killer) = value {
+          killer_player_id = Some(*killer as u8);
+        },
+        "m_killerUnitTagIndex" => if let DecoderResult::Value(index) = value {
+          killer_tag_index = Some(*index as u32);
+        },
+        "m_killerUnitTagRecycle" => if let DecoderResult::Value(recycle) = value {
+          killer_tag_recycle = Some(*recycle as u32);
+        },
+        // the building or unit that produced a born unit
+        "m_creatorUnitTagIndex" => if let DecoderResult::Value(index) = value {
+          creator_tag_index = Some(*index as u32);
+        },
+        "m_creatorUnitTagRecycle" => if let DecoderResult::Value(recycle) = value {
+          creator_tag_recycle = Some(*recycle as u32);
         },
-        // "m_killerUnitTagIndex" => if let DecoderResult::Blob
         "m_unitTagIndex" => if let DecoderResult::Value(index) = value {
           tag_index = *index as u32;
         },
@@ -206,133 +540,305 @@ impl ObjectEvent {
           tag_recycle = *recycle as u32;
         },
         "_gameloop" => if let DecoderResult::Value(gameloop) = value {
-          current_gameloop = *gameloop as u16;
+          current_gameloop = Gameloop(*gameloop as u32);
+        },
+        "m_x" => if let DecoderResult::Value(x) = value {
+          event_x = Some(*x as u16);
+        },
+        "m_y" => if let DecoderResult::Value(y) = value {
+          event_y = Some(*y as u16);
         },
         _other => continue,
       }
     }
 
+    let tag_id = (tag_index << 18) + tag_recycle;
+    let event_position = event_x.zip(event_y);
+    let killer_tag_id = killer_tag_index
+      .zip(killer_tag_recycle)
+      .map(|(index, recycle)| (index << 18) + recycle);
+    let creator_tag_id = creator_tag_index
+      .zip(creator_tag_recycle)
+      .map(|(index, recycle)| (index << 18) + recycle);
+
+    if event_name == "NNet.Replay.Tracker.SUnitDoneEvent" {
+      if let Some(game_object) = game.find_object(tag_id) {
+        game_object.done_gameloop = Some(current_gameloop);
+        game_object.supply_provided = supply_provided(&names[game_object.object_name_idx]).unwrap_or(0.0);
+
+        let (supply_owner, supply_provided) = (game_object.player_id, game_object.supply_provided);
+
+        // neutral and hostile objects aren't part of any build
+        if let Some(player_index) = (game_object.owner_at(game_object.init_gameloop) as usize)
+          .checked_sub(1)
+          .filter(|player_index| *player_index < game.builds.len())
+        {
+          if let Some(build_entry) = game.builds[player_index]
+            .iter_mut()
+            .chain(game.units[player_index].iter_mut())
+            .find(|build_entry| build_entry.tag_id == tag_id)
+          {
+            build_entry.finished = Some(current_gameloop);
+          }
+        }
+
+        if let Some(supply) = (supply_owner as usize)
+          .checked_sub(1)
+          .and_then(|player_index| game.supply.get_mut(player_index))
+        {
+          supply.update(current_gameloop, 0.0, supply_provided);
+        }
+      }
+
+      return Ok(());
+    }
+
     if event_name == "NNet.Replay.Tracker.SUnitDiedEvent" {
-      match game.objects.binary_search_by(|obj| obj.tag_index.cmp(&tag_index)) {
-        Ok(idx) => {
-          game.objects.remove(idx);
-          ()
-        },
-        Err(_) => (),
+      // dead objects are kept so their position history can still be queried
+      if let Some(game_object) = game.find_object(tag_id) {
+        game_object.died_gameloop = Some(current_gameloop);
+        game_object.killer_player_id = killer_player_id;
+        if let Some((x, y)) = event_position {
+          game_object.positions.push((current_gameloop, x, y));
+        }
+
+        let (owner_id, supply_used, supply_provided) = (
+          game_object.player_id,
+          game_object.supply,
+          game_object.supply_provided,
+        );
+        let object_name = &names[game_object.object_name_idx];
+        let worker_name = match WORKERS.contains(&object_name.as_str()) {
+          true => Some(object_name.to_owned()),
+          false => None,
+        };
+
+        // buildings and warp-ins that die before finishing were cancelled
+        // or destroyed under construction, so they aren't part of the build
+        if game_object.done_gameloop.is_none() {
+          if let Some(player_index) = (game_object.owner_at(game_object.init_gameloop) as usize)
+            .checked_sub(1)
+            .filter(|player_index| *player_index < game.builds.len())
+          {
+            game.builds[player_index].retain(|build_entry| build_entry.tag_id != tag_id);
+            game.units[player_index].retain(|unit_entry| unit_entry.tag_id != tag_id);
+          }
+        }
+
+        if let Some(supply) = (owner_id as usize)
+          .checked_sub(1)
+          .and_then(|player_index| game.supply.get_mut(player_index))
+        {
+          supply.update(current_gameloop, -supply_used, -supply_provided);
+        }
+
+        // drones morphing into buildings die without a killer
+        if let (Some(worker_name), Some(killer_player_id)) = (worker_name, killer_player_id) {
+          let killer_name = killer_tag_id
+            .and_then(|killer_tag_id| game.find_object(killer_tag_id))
+            .map(|killer| names[killer.object_name_idx].to_owned());
+
+          if let Some(player_index) = (owner_id as usize)
+            .checked_sub(1)
+            .filter(|player_index| *player_index < game.worker_deaths.len())
+          {
+            game.worker_deaths[player_index].push(WorkerDeath {
+              name: worker_name,
+              gameloop: current_gameloop,
+              killer_player_id,
+              killer_name,
+            });
+            context.workers_lost[player_index] += 1;
+          }
+
+          // friendly fire and neutral kills aren't credited to anyone
+          if let Some(killer_index) = (killer_player_id as usize)
+            .checked_sub(1)
+            .filter(|killer_index| *killer_index < context.workers_killed.len() && killer_player_id != owner_id)
+          {
+            context.workers_killed[killer_index] += 1;
+          }
+        }
       }
+
+      return Ok(());
     }
 
-    if event_object_name == "" {
+    if event_object_name.is_empty() {
       return Err("Object name not found");
     }
 
+    let event_object_name_idx = match names.iter().position(|name| name == event_object_name) {
+      Some(idx) => idx,
+      None => {
+        names.push(event_object_name.to_string());
+        names.len() - 1
+      },
+    };
+
     // if !game.objects.contains_key(&tag_index) {
     // if let None = game.objects.iter().find(|obj| obj.tag_index == tag_index) {
-    let mut game_object = match game.objects.binary_search_by(|obj| obj.tag_index.cmp(&tag_index)) {
+    let mut created = false;
+    let game_object = match game.objects.binary_search_by(|obj| obj.tag_id.cmp(&tag_id)) {
       Ok(idx) => &mut game.objects[idx],
       Err(idx) => {
-        let tag_id = (tag_index << 18) + tag_recycle;
-
-        let mut object_name_idx: i16 = -1;
-        for (idx, name) in names.iter().enumerate() {
-          if *name == event_object_name {
-            object_name_idx = idx as i16;
-            break;
-          }
-        }
 
-        if object_name_idx == -1 {
-          names.push(event_object_name.to_string());
-          object_name_idx = names.len() as i16 - 1;
-        }
+        // objects that are born instead of initialized are already complete
+        let done_gameloop = if event_name == "NNet.Replay.Tracker.SUnitInitEvent" {
+          None
+        } else {
+          Some(current_gameloop)
+        };
 
         let new_object = GameObject {
-          object_name_idx: object_name_idx as usize,
+          object_name_idx: event_object_name_idx,
           object_type: event_object_type,
           tag_id,
           tag_index,
-          tag_recycle,
           player_id,
+          owners: vec![(current_gameloop, player_id)],
+          init_gameloop: current_gameloop,
+          done_gameloop,
+          died_gameloop: None,
+          positions: vec![],
+          killer_player_id: None,
+          creator_tag_id,
+          types: vec![(current_gameloop, event_object_name_idx)],
+          supply: supply_cost(event_object_name).unwrap_or(0.0),
+          supply_provided: match done_gameloop {
+            Some(_) => supply_provided(event_object_name).unwrap_or(0.0),
+            None => 0.0,
+          },
         };
 
+        created = true;
+
         game.objects.insert(idx, new_object);
         &mut game.objects[idx]
       },
     };
 
-    let player_index = game_object.player_id - 1;
-    let mut game_object_name = &names[game_object.object_name_idx];
+    if let Some((x, y)) = event_position {
+      game_object.positions.push((current_gameloop, x, y));
+    }
 
-    if player_index > 1 {
-      return Err("More than 2 players in replay");
+    // neutral objects like minerals and rocks are only tracked for their positions
+    if game_object.player_id == 0 {
+      return Ok(());
+    }
+
+    // hostile map units are owned by ids past the last player
+    let player_index = game_object.player_id - 1;
+    if player_index as usize >= game.builds.len() {
+      return Ok(());
     }
+    let mut game_object_name = &names[game_object.object_name_idx];
 
     let transition = (game_object_name.as_str(), event_object_name);
     let mut calculated_gameloop = current_gameloop;
+    let mut finished_gameloop = game_object.done_gameloop;
+
+    if created && WORKERS.contains(&game_object_name.as_str()) {
+      game.worker_births[player_index as usize].push(current_gameloop);
+    }
+
+    let mut supply_change = match created {
+      true => (game_object.supply, game_object.supply_provided),
+      false => (0.0, 0.0),
+    };
 
+    // morphs like Roach -> Ravager change supply, alternate modes don't
     if event_name == "NNet.Replay.Tracker.SUnitTypeChangeEvent" {
-      if ALLOWED_TRANSITIONS.contains(&transition) {
-        let mut new_object_name_idx: i16 = -1;
-        for (idx, name) in names.iter().enumerate() {
-          if *name == event_object_name {
-            new_object_name_idx = idx as i16;
-            break;
-          }
-        }
+      game_object.types.push((current_gameloop, event_object_name_idx));
 
-        if new_object_name_idx == -1 {
-          names.push(event_object_name.to_string());
-          new_object_name_idx = names.len() as i16 - 1;
-        }
-        game_object.object_name_idx = new_object_name_idx as usize;
-        game_object_name = &names[game_object.object_name_idx];
+      if let Some(supply) = supply_cost(event_object_name) {
+        supply_change.0 += supply - game_object.supply;
+        game_object.supply = supply;
+      }
+
+      if let (Some(_), Some(provided)) = (game_object.done_gameloop, supply_provided(event_object_name)) {
+        supply_change.1 += provided - game_object.supply_provided;
+        game_object.supply_provided = provided;
+      }
+    }
 
-        let transition_object= TRANSITION_BUILD_TIMES
-          .iter()
-          .find(|(name, _)| *name == game_object_name);
+    // supply when the event happened, before this object is accounted for
+    let entry_supply = game.supply[player_index as usize].used_at(current_gameloop) as u16;
+
+    // trained units use supply from when they're queued, chrono boost makes
+    // some start a little later than this
+    let supply_gameloop = match (created, event_name.as_str(), production_time(game_object_name)) {
+      (true, "NNet.Replay.Tracker.SUnitBornEvent", Some(gameloops)) => current_gameloop.saturating_sub(gameloops),
+      _other => current_gameloop,
+    };
+    game.supply[player_index as usize].update(supply_gameloop, supply_change.0, 0.0);
+    game.supply[player_index as usize].update(current_gameloop, 0.0, supply_change.1);
 
-        calculated_gameloop = match transition_object {
-          Some((_, transition_gameloops)) => current_gameloop - transition_gameloops,
+    if event_name == "NNet.Replay.Tracker.SUnitTypeChangeEvent" {
+      if ALLOWED_TRANSITIONS.contains(&transition) {
+        game_object.object_name_idx = event_object_name_idx;
+        game_object_name = &names[game_object.object_name_idx];
+
+        calculated_gameloop = match transition_time(game_object_name) {
+          Some(transition_gameloops) => current_gameloop.saturating_sub(transition_gameloops),
           None => current_gameloop,
         };
+
+        // type changes are recorded when the morph finishes
+        finished_gameloop = Some(current_gameloop);
       } else {
         return Ok(());
       }
     }
 
-    if
-      event_name == "NNet.Replay.Tracker.SUnitDiedEvent" &&
-      game_object.object_type == ObjectType::Unit &&
-      WORKERS.contains(&game_object_name.as_str())
-      // and obj killed by something, drones can die morphing
-    {
-      let opponent_index: usize = if player_index == 1 {
-        0
-      } else {
-        1
-      };
-      context.workers_lost[player_index as usize] += 1;
-      context.workers_killed[opponent_index] += 1;
-    }
+    let is_building = game_object.object_type == ObjectType::Building;
+    let is_unit = game_object.object_type == ObjectType::Unit;
 
-    // 9408 = ~7min, 22.4 gameloops per sec
     if
-      calculated_gameloop > 0 &&
-      calculated_gameloop < 9408
+      calculated_gameloop > Gameloop(0) &&
+      calculated_gameloop < BUILD_WINDOW
     {
       // I think the partial eq here is super inefficient
-      if game_object.object_type == ObjectType::Building &&
+      if is_building &&
         !(game_object_name.contains("Reactor") || game_object_name.contains("TechLab")) &&
         game.builds[player_index as usize].len() < MAX_BUILD_LENGTH as usize
       {
-        game.builds[player_index as usize].push((game_object_name.to_owned(), calculated_gameloop));
+        game.builds[player_index as usize].push(BuildEntry {
+          name: game_object_name.to_owned(),
+          started: calculated_gameloop,
+          finished: finished_gameloop,
+          // set by Game::mark_proxies once the natural is known
+          proxy: false,
+          supply: entry_supply,
+          tag_id,
+        });
       }
 
-      // if game_object.object_type == "unit" &&
-      //   game.units[player_index as usize].len() < MAX_UNIT_BUILD_LENGTH as usize
-      // {
-      //   game.units[player_index as usize].push((game_object.object_name.to_string(), calculated_gameloop));
-      // }
+      // workers are units but aren't part of unit compositions
+      if is_unit &&
+        UNITS.contains(&game_object_name.as_str()) &&
+        game.units[player_index as usize].len() < MAX_UNIT_BUILD_LENGTH as usize
+      {
+        let player_units = &game.units[player_index as usize];
+        let mut unit_types: HashSet<&str> = HashSet::new();
+        for unit_entry in player_units {
+          unit_types.insert(&unit_entry.name);
+        }
+
+        if
+          unit_types.contains(game_object_name.as_str()) ||
+          unit_types.len() < MAX_UNIT_TYPES as usize
+        {
+          game.units[player_index as usize].push(BuildEntry {
+            name: game_object_name.to_owned(),
+            started: calculated_gameloop,
+            finished: finished_gameloop,
+            proxy: false,
+            supply: entry_supply,
+            tag_id,
+          });
+        }
+      }
     }
 
     Ok(())
@@ -0,0 +1,54 @@
+use crate::replay::Event;
+use crate::decoders::DecoderResult;
+use crate::game::Game;
+use crate::time::Gameloop;
+
+pub struct OwnerChangeEvent;
+
+impl OwnerChangeEvent {
+  pub fn handle(
+    game: &mut Game,
+    event: &Event,
+  ) -> Result<(), &'static str> {
+    let mut player_id: u8 = 0;
+    let mut tag_index: u32 = 0;
+    let mut tag_recycle: u32 = 0;
+    let mut gameloop = Gameloop(0);
+
+    for (field, value) in &event.entries {
+      match field.as_str() {
+        "m_controlPlayerId" => player_id = if let DecoderResult::Value(v) = value {
+          *v as u8
+        } else {
+          return Err("Player ID is not a value");
+        },
+        "m_unitTagIndex" => if let DecoderResult::Value(index) = value {
+          tag_index = *index as u32;
+        },
+        "m_unitTagRecycle" => if let DecoderResult::Value(recycle) = value {
+          tag_recycle = *recycle as u32;
+        },
+        "_gameloop" => if let DecoderResult::Value(v) = value {
+          gameloop = Gameloop(*v as u32);
+        },
+        _other => continue,
+      }
+    }
+
+    let tag_id = (tag_index << 18) + tag_recycle;
+    // objects are only tracked when their init or born events are decoded
+    let game_object = match game.find_object(tag_id) {
+      Some(game_object) => game_object,
+      None => return Ok(()),
+    };
+
+    // neural parasite, captures and map triggers can
+    // transfer objects multiple times during the game
+    if game_object.player_id != player_id {
+      game_object.player_id = player_id;
+      game_object.owners.push((gameloop, player_id));
+    }
+
+    Ok(())
+  }
+}
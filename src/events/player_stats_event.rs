@@ -1,26 +1,30 @@
 use crate::TinybirdTimelineEntry;
 use crate::parser::TimelineContext;
 use crate::replay::Event;
-use crate::decoders::DecoderResult;
-use crate::game::Game;
+use crate::decoders::{DecoderResult, EventEntry};
+use crate::game::{Game, Score};
+use crate::time::Gameloop;
+
+// food values are fixed point with 12 fractional bits
+const FOOD_SCALE: f32 = 4096.0;
 
 pub struct PlayerStatsEvent;
 
 impl PlayerStatsEvent {
-  pub fn new(
+  pub fn handle(
     context: &TimelineContext,
     game: &mut Game,
     timeline: &mut Vec<TinybirdTimelineEntry>,
     event: &Event,
   ) -> Result<(), &'static str> {
     let mut player_id: u8 = 0;
-    let mut gameloop: u16 = 0;
-    let mut timeline_entry: TinybirdTimelineEntry = Default::default();
+    let mut gameloop = Gameloop(0);
+    let mut score: Option<Score> = None;
 
     for (field, value) in &event.entries {
       match field.as_str() {
         "_gameloop" => gameloop = if let DecoderResult::Value(v) = value {
-          *v as u16
+          Gameloop(*v as u32)
         } else {
           return Err("No gameloop present");
         },
@@ -30,123 +34,7 @@ impl PlayerStatsEvent {
           return Err("Player ID is not a value");
         },
         "m_stats" => if let DecoderResult::Struct(entries) = value {
-          let player_index = (player_id - 1) as usize;
-
-          let mut event_minerals_collected: i64 = 0;
-          let mut event_minerals_lost: i64 = 0;
-
-          let mut event_gas_collected: i64 = 0;
-          let mut event_gas_lost: i64 = 0;
-
-          let mut event_minerals_collection_rate: u16 = 0;
-          let mut event_gas_collection_rate: u16 = 0;
-
-          let mut event_minerals_unspent_resources: u16 = 0;
-          let mut event_gas_unspent_resources: u16 = 0;
-
-          let mut event_minerals_army_value: u16 = 0;
-          let mut event_gas_army_value: u16 = 0;
-
-          // don't support more than 2 players
-          if player_index > 1 {
-            return Err("More than 1 player in replay");
-          }
-
-          for (key, value) in entries {
-            match key.as_str() {
-              "m_scoreValueWorkersActiveCount" => if let DecoderResult::Value(workers) = value {
-                game.workers_active[player_index] = *workers as u8;
-              },
-              "m_scoreValueMineralsCollectionRate" => if let DecoderResult::Value(minerals) = value {
-                event_minerals_collection_rate = *minerals as u16;
-              },
-              "m_scoreValueVespeneCollectionRate" => if let DecoderResult::Value(gas) = value {
-                event_gas_collection_rate = *gas as u16;
-              },
-              "m_scoreValueMineralsCurrent" => if let DecoderResult::Value(minerals) = value {
-                event_minerals_unspent_resources = *minerals as u16;
-                event_minerals_collected += minerals;
-              },
-              "m_scoreValueVespeneCurrent" => if let DecoderResult::Value(gas) = value {
-                event_gas_unspent_resources = *gas as u16;
-                event_gas_collected += gas;
-              },
-              "m_scoreValueMineralsLostArmy" |
-              "m_scoreValueMineralsLostEconomy" |
-              "m_scoreValueMineralsLostTechnology" => if let DecoderResult::Value(minerals) = value {
-                event_minerals_lost += minerals.abs();
-                event_minerals_collected += minerals;
-              }
-              "m_scoreValueVespeneLostArmy" |
-              "m_scoreValueVespeneLostEconomy" |
-              "m_scoreValueVespeneLostTechnology" => if let DecoderResult::Value(gas) = value {
-                event_gas_lost += gas.abs();
-                event_gas_collected += gas;
-              }
-              // "m_scoreValueMineralsUsedInProgressArmy" |
-              // "m_scoreValueMineralsUsedInProgressEconomy" |
-              // "m_scoreValueMineralsUsedInProgressTechnology" |
-              // "m_scoreValueMineralsUsedCurrentArmy" |
-              // "m_scoreValueMineralsUsedCurrentEconomy" |
-              // "m_scoreValueMineralsUsedCurrentTechnology" => if let DecoderResult::Value(minerals) = value {
-              //   event_minerals_collected += minerals;
-              // },
-              // "m_scoreValueVespeneUsedInProgressArmy" |
-              // "m_scoreValueVespeneUsedInProgressEconomy" |
-              // "m_scoreValueVespeneUsedInProgressTechnology" |
-              // "m_scoreValueVespeneUsedCurrentArmy" |
-              // "m_scoreValueVespeneUsedCurrentEconomy" |
-              // "m_scoreValueVespeneUsedCurrentTechnology" => if let DecoderResult::Value(gas) = value {
-              //   event_gas_collected += gas;
-              // },
-              "m_scoreValueMineralsUsedInProgressArmy" |
-              "m_scoreValueMineralsUsedCurrentArmy" => if let DecoderResult::Value(minerals) = value {
-                event_minerals_army_value = *minerals as u16;
-              },
-              "m_scoreValueVespeneUsedInProgressArmy" |
-              "m_scoreValueVespeneUsedCurrentArmy" => if let DecoderResult::Value(gas) = value {
-                event_gas_army_value = *gas as u16;
-              },
-              _other => continue,
-            }
-          }
-
-          game.minerals_collected[player_index] = event_minerals_collected as u16;
-          game.minerals_lost[player_index] = event_minerals_lost as u16;
-
-          game.gas_collected[player_index] = event_gas_collected as u16;
-          game.gas_lost[player_index] = event_gas_lost as u16;
-
-          game.collection_rate[player_index].push((event_minerals_collection_rate, event_gas_collection_rate));
-          game.unspent_resources[player_index].push((event_minerals_unspent_resources, event_gas_unspent_resources));
-
-          let win = if context.winner_id == player_id {
-            1
-          } else {
-            0
-          };
-
-          // let timeline_state: TinybirdTimelineEntry = TinybirdTimelineEntry {
-          //   content_hash: context.content_hash.clone(),
-          //   win,
-          //   player: context.players[player_index].name.clone(),
-          //   player_race: context.players[player_index].race.clone(),
-          //   // player_build: context.players[player_index].build,
-          //   player_collection_rate: event_minerals_collection_rate + event_gas_collection_rate,
-          //   player_army_value: event_minerals_army_value + event_gas_army_value,
-          //   player_workers_active: game.workers_active[player_index] as u16,
-          //   // player_workers_lost: (),
-          //   // player_workers_killed: (),
-          //   matchup: context.matchup.clone(),
-          //   map: context.map.clone(),
-          //   event: context.event.clone(),
-          //   game_length: context.game_length,
-          //   played_at: context.played_at,
-          //   game_version: context.game_version.clone(),
-          //   ..Default::default()
-          // };
-
-          // timeline_entry = timeline_state;
+          score = Some(PlayerStatsEvent::parse_score(entries));
         } else {
           panic!("didn't find struct {:?}",  value);
         },
@@ -154,27 +42,168 @@ impl PlayerStatsEvent {
       }
     }
 
-    // // event might be encountered before gameloop
-    // timeline_entry.gameloop = gameloop;
+    // event might be encountered before gameloop
+    let mut score = match score {
+      Some(score) => score,
+      None => return Err("No stats present"),
+    };
+    score.gameloop = gameloop;
+
+    // observers don't have stats worth keeping
+    let player = match context.players.iter().find(|player| player.id == player_id) {
+      Some(player) => player,
+      None => return Ok(()),
+    };
+    let player_index = (player_id - 1) as usize;
+
+    game.workers_active[player_index] = score.workers_active as u8;
+
+    game.minerals_collected[player_index] = score.collected().minerals;
+    game.minerals_lost[player_index] = score.lost.total().minerals;
+
+    game.gas_collected[player_index] = score.collected().gas;
+    game.gas_lost[player_index] = score.lost.total().gas;
+
+    game.collection_rate[player_index].push((score.collection_rate.minerals, score.collection_rate.gas));
+    game.unspent_resources[player_index].push((score.current.minerals, score.current.gas));
+
+    let win = if context.winning_team == Some(player.team) {
+      1
+    } else {
+      0
+    };
+
+    let mut timeline_entry = TinybirdTimelineEntry {
+      content_hash: context.content_hash.clone(),
+      gameloop,
+      win,
+      player_team: player.team,
+      player: player.name.clone(),
+      player_race: player.race.clone(),
+      // player_build: context.players[player_index].build,
+      player_collection_rate: score.collection_rate.total(),
+      player_army_value: score.army_value().total(),
+      player_workers_active: score.workers_active as u16,
+      player_supply_used: score.food_used as u16,
+      player_supply_made: score.food_made as u16,
+      player_resources_lost: score.lost.total().total(),
+      player_resources_killed: score.killed.total().total(),
+      player_workers_lost: context.workers_lost[player_index],
+      player_workers_killed: context.workers_killed[player_index],
+      matchup: context.matchup.clone(),
+      map: context.map.clone(),
+      event: context.event.clone(),
+      game_length: context.game_length,
+      played_at: context.played_at,
+      game_version: context.game_version.clone(),
+      ..Default::default()
+    };
+
+    game.scores[player_index].push(score);
+
+    // every player has a stats event on the same gameloop, pair the new entry
+    // with an opposing entry already recorded for this gameloop. in team games
+    // each player is paired with the first opponent that hasn't been paired yet
+    for previous_timeline_entry in timeline
+      .iter_mut()
+      .rev()
+      .take_while(|previous_timeline_entry| previous_timeline_entry.gameloop == gameloop)
+      .filter(|previous_timeline_entry|
+        previous_timeline_entry.player_team != timeline_entry.player_team &&
+        previous_timeline_entry.opponent.is_empty()
+      )
+      .take(1)
+    {
+      timeline_entry.opponent = previous_timeline_entry.player.clone();
+      timeline_entry.opponent_race = previous_timeline_entry.player_race.clone();
+      timeline_entry.opponent_collection_rate = previous_timeline_entry.player_collection_rate;
+      timeline_entry.opponent_army_value = previous_timeline_entry.player_army_value;
+      timeline_entry.opponent_workers_active = previous_timeline_entry.player_workers_active;
+      timeline_entry.opponent_supply_used = previous_timeline_entry.player_supply_used;
+      timeline_entry.opponent_supply_made = previous_timeline_entry.player_supply_made;
+      timeline_entry.opponent_resources_lost = previous_timeline_entry.player_resources_lost;
+      timeline_entry.opponent_resources_killed = previous_timeline_entry.player_resources_killed;
+      timeline_entry.opponent_workers_lost = previous_timeline_entry.player_workers_lost;
+      timeline_entry.opponent_workers_killed = previous_timeline_entry.player_workers_killed;
+
+      previous_timeline_entry.opponent = timeline_entry.player.clone();
+      previous_timeline_entry.opponent_race = timeline_entry.player_race.clone();
+      previous_timeline_entry.opponent_collection_rate = timeline_entry.player_collection_rate;
+      previous_timeline_entry.opponent_army_value = timeline_entry.player_army_value;
+      previous_timeline_entry.opponent_workers_active = timeline_entry.player_workers_active;
+      previous_timeline_entry.opponent_supply_used = timeline_entry.player_supply_used;
+      previous_timeline_entry.opponent_supply_made = timeline_entry.player_supply_made;
+      previous_timeline_entry.opponent_resources_lost = timeline_entry.player_resources_lost;
+      previous_timeline_entry.opponent_resources_killed = timeline_entry.player_resources_killed;
+      previous_timeline_entry.opponent_workers_lost = timeline_entry.player_workers_lost;
+      previous_timeline_entry.opponent_workers_killed = timeline_entry.player_workers_killed;
+    }
 
-    // if let Some(previous_timeline_entry) = timeline.last_mut() {
-    //   if previous_timeline_entry.gameloop == gameloop {
-    //     timeline_entry.opponent = previous_timeline_entry.player.clone();
-    //     timeline_entry.opponent_race = previous_timeline_entry.player_race.clone();
-    //     timeline_entry.opponent_collection_rate = previous_timeline_entry.player_collection_rate.clone();
-    //     timeline_entry.opponent_army_value = previous_timeline_entry.player_army_value.clone();
-    //     timeline_entry.opponent_workers_active = previous_timeline_entry.player_workers_active.clone();
+    timeline.push(timeline_entry);
 
-    //     previous_timeline_entry.opponent = timeline_entry.player.clone();
-    //     previous_timeline_entry.opponent_race = timeline_entry.player_race.clone();
-    //     previous_timeline_entry.opponent_collection_rate = timeline_entry.player_collection_rate.clone();
-    //     previous_timeline_entry.opponent_army_value = timeline_entry.player_army_value.clone();
-    //     previous_timeline_entry.opponent_workers_active = timeline_entry.player_workers_active.clone();
-    //   }
-    // }
+    Ok(())
+  }
 
-    // timeline.push(timeline_entry);
+  fn parse_score(entries: &[EventEntry]) -> Score {
+    let mut score: Score = Default::default();
 
-    Ok(())
+    for (key, value) in entries {
+      let value = match value {
+        DecoderResult::Value(value) => *value,
+        _other => continue,
+      };
+
+      match key.as_str() {
+        "m_scoreValueMineralsCurrent" => score.current.minerals = value,
+        "m_scoreValueVespeneCurrent" => score.current.gas = value,
+        "m_scoreValueMineralsCollectionRate" => score.collection_rate.minerals = value,
+        "m_scoreValueVespeneCollectionRate" => score.collection_rate.gas = value,
+        "m_scoreValueWorkersActiveCount" => score.workers_active = value,
+
+        "m_scoreValueMineralsUsedInProgressArmy" => score.used_in_progress.army.minerals = value,
+        "m_scoreValueMineralsUsedInProgressEconomy" => score.used_in_progress.economy.minerals = value,
+        "m_scoreValueMineralsUsedInProgressTechnology" => score.used_in_progress.technology.minerals = value,
+        "m_scoreValueVespeneUsedInProgressArmy" => score.used_in_progress.army.gas = value,
+        "m_scoreValueVespeneUsedInProgressEconomy" => score.used_in_progress.economy.gas = value,
+        "m_scoreValueVespeneUsedInProgressTechnology" => score.used_in_progress.technology.gas = value,
+
+        "m_scoreValueMineralsUsedCurrentArmy" => score.used_current.army.minerals = value,
+        "m_scoreValueMineralsUsedCurrentEconomy" => score.used_current.economy.minerals = value,
+        "m_scoreValueMineralsUsedCurrentTechnology" => score.used_current.technology.minerals = value,
+        "m_scoreValueVespeneUsedCurrentArmy" => score.used_current.army.gas = value,
+        "m_scoreValueVespeneUsedCurrentEconomy" => score.used_current.economy.gas = value,
+        "m_scoreValueVespeneUsedCurrentTechnology" => score.used_current.technology.gas = value,
+
+        // lost values are sometimes recorded as negative
+        "m_scoreValueMineralsLostArmy" => score.lost.army.minerals = value.abs(),
+        "m_scoreValueMineralsLostEconomy" => score.lost.economy.minerals = value.abs(),
+        "m_scoreValueMineralsLostTechnology" => score.lost.technology.minerals = value.abs(),
+        "m_scoreValueVespeneLostArmy" => score.lost.army.gas = value.abs(),
+        "m_scoreValueVespeneLostEconomy" => score.lost.economy.gas = value.abs(),
+        "m_scoreValueVespeneLostTechnology" => score.lost.technology.gas = value.abs(),
+
+        "m_scoreValueMineralsKilledArmy" => score.killed.army.minerals = value,
+        "m_scoreValueMineralsKilledEconomy" => score.killed.economy.minerals = value,
+        "m_scoreValueMineralsKilledTechnology" => score.killed.technology.minerals = value,
+        "m_scoreValueVespeneKilledArmy" => score.killed.army.gas = value,
+        "m_scoreValueVespeneKilledEconomy" => score.killed.economy.gas = value,
+        "m_scoreValueVespeneKilledTechnology" => score.killed.technology.gas = value,
+
+        "m_scoreValueMineralsFriendlyFireArmy" => score.friendly_fire.army.minerals = value,
+        "m_scoreValueMineralsFriendlyFireEconomy" => score.friendly_fire.economy.minerals = value,
+        "m_scoreValueMineralsFriendlyFireTechnology" => score.friendly_fire.technology.minerals = value,
+        "m_scoreValueVespeneFriendlyFireArmy" => score.friendly_fire.army.gas = value,
+        "m_scoreValueVespeneFriendlyFireEconomy" => score.friendly_fire.economy.gas = value,
+        "m_scoreValueVespeneFriendlyFireTechnology" => score.friendly_fire.technology.gas = value,
+
+        "m_scoreValueFoodUsed" => score.food_used = value as f32 / FOOD_SCALE,
+        "m_scoreValueFoodMade" => score.food_made = value as f32 / FOOD_SCALE,
+        "m_scoreValueMineralsUsedActiveForces" => score.used_active_forces.minerals = value,
+        "m_scoreValueVespeneUsedActiveForces" => score.used_active_forces.gas = value,
+        _other => continue,
+      }
+    }
+
+    score
   }
 }
@@ -0,0 +1,52 @@
+use crate::replay::Event;
+use crate::decoders::DecoderResult;
+use crate::game::Game;
+use crate::time::Gameloop;
+
+pub struct PositionsEvent;
+
+impl PositionsEvent {
+  pub fn handle(
+    game: &mut Game,
+    event: &Event,
+  ) -> Result<(), &'static str> {
+    let mut unit_index: u32 = 0;
+    let mut items: &[DecoderResult] = &[];
+    let mut gameloop = Gameloop(0);
+
+    for (field, value) in &event.entries {
+      match field.as_str() {
+        "m_firstUnitIndex" => if let DecoderResult::Value(index) = value {
+          unit_index = *index as u32;
+        },
+        "m_items" => if let DecoderResult::Array(values) = value {
+          items = values;
+        },
+        "_gameloop" => if let DecoderResult::Value(v) = value {
+          gameloop = Gameloop(*v as u32);
+        },
+        _other => continue,
+      }
+    }
+
+    // items are packed as (unit index delta, x, y)
+    // positions are recorded at 1/4 resolution of map cells
+    for item in items.chunks(3) {
+      match item {
+        [
+          DecoderResult::DataFragment(index_delta),
+          DecoderResult::DataFragment(x),
+          DecoderResult::DataFragment(y),
+        ] => {
+          unit_index += index_delta;
+          if let Some(game_object) = game.find_live_object(unit_index) {
+            game_object.positions.push((gameloop, (x * 4) as u16, (y * 4) as u16));
+          }
+        },
+        _other => return Err("Unit position is not a value"),
+      }
+    }
+
+    Ok(())
+  }
+}
@@ -0,0 +1,67 @@
+use crate::parser::TimelineContext;
+use crate::replay::Event;
+use crate::decoders::DecoderResult;
+use crate::game::Game;
+use crate::time::Gameloop;
+
+// cosmetic upgrades granted to players at the start of the game
+const IGNORED_UPGRADE_PREFIXES: [&str; 3] = [
+  "Spray",
+  "Reward",
+  "GameHeart",
+];
+
+pub struct UpgradeEvent;
+
+impl UpgradeEvent {
+  pub fn handle(
+    context: &TimelineContext,
+    game: &mut Game,
+    event: &Event,
+  ) -> Result<(), &'static str> {
+    let mut player_id: u8 = 0;
+    let mut upgrade_name = "";
+    let mut count: i64 = 0;
+    let mut gameloop = Gameloop(0);
+
+    for (field, value) in &event.entries {
+      match field.as_str() {
+        "m_playerId" => player_id = if let DecoderResult::Value(v) = value {
+          *v as u8
+        } else {
+          return Err("Player ID is not a value");
+        },
+        "m_upgradeTypeName" => if let DecoderResult::Blob(name) = value {
+          upgrade_name = name;
+        },
+        "m_count" => if let DecoderResult::Value(v) = value {
+          count = *v;
+        },
+        "_gameloop" => if let DecoderResult::Value(v) = value {
+          gameloop = Gameloop(*v as u32);
+        },
+        _other => continue,
+      }
+    }
+
+    if
+      gameloop == Gameloop(0) ||
+      count <= 0 ||
+      upgrade_name.is_empty() ||
+      IGNORED_UPGRADE_PREFIXES.iter().any(|prefix| upgrade_name.starts_with(prefix))
+    {
+      return Ok(());
+    }
+
+    // map triggers can grant upgrades to ids that aren't players
+    if !context.players.iter().any(|player| player.id == player_id) {
+      return Ok(());
+    }
+
+    let player_index = (player_id - 1) as usize;
+
+    game.upgrades[player_index].push((upgrade_name.to_string(), gameloop));
+
+    Ok(())
+  }
+}
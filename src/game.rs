@@ -1,4 +1,7 @@
-use crate::events::object_event::ObjectType;
+use crate::events::object_event::{object_cost, ObjectType, TOWN_HALLS};
+use crate::time::Gameloop;
+
+use serde::Serialize;
 
 #[derive(Debug)]
 pub struct GameObject {
@@ -6,66 +9,662 @@ pub struct GameObject {
   pub object_type: ObjectType,
   pub tag_id: u32,
   pub tag_index: u32,
-  pub tag_recycle: u32,
+  // current owner, previous owners are kept in owners
   pub player_id: u8,
+  // (gameloop, player_id) for every change in ownership
+  pub owners: Vec<(Gameloop, u8)>,
+  pub init_gameloop: Gameloop,
+  // None while the object is still under construction
+  pub done_gameloop: Option<Gameloop>,
+  pub died_gameloop: Option<Gameloop>,
+  pub killer_player_id: Option<u8>,
+  // production building that trained the unit
+  pub creator_tag_id: Option<u32>,
+  // (gameloop, index into names) for the created type and every type
+  // change, including alternate modes and morphs that aren't part of builds
+  pub types: Vec<(Gameloop, usize)>,
+  // (gameloop, x, y) in map cells
+  pub positions: Vec<(Gameloop, u16, u16)>,
+  // supply the object currently uses and provides
+  pub supply: f32,
+  pub supply_provided: f32,
+}
+
+impl GameObject {
+  // falls back to the current name for objects with no recorded types
+  pub fn name_at<'a>(&'a self, names: &'a [String], gameloop: Gameloop) -> &'a str {
+    match self.types.iter().rev().find(|(type_gameloop, _)| *type_gameloop <= gameloop) {
+      Some((_, name_idx)) => &names[*name_idx],
+      None => &names[self.object_name_idx],
+    }
+  }
+
+  // where a town hall ended up, which for command centers flown to
+  // another base is the first position after landing
+  pub fn base_position(&self, names: &[String]) -> Option<(u16, u16)> {
+    let is_flying = |name_idx: usize| names[name_idx].ends_with("Flying");
+    let landed_gameloop = self.types
+      .windows(2)
+      .filter(|types| is_flying(types[0].1) && !is_flying(types[1].1))
+      .map(|types| types[1].0)
+      .next_back();
+
+    let position = match landed_gameloop {
+      Some(landed_gameloop) => self.positions
+        .iter()
+        .find(|(gameloop, _, _)| *gameloop >= landed_gameloop)
+        .or(self.positions.first()),
+      None => self.positions.first(),
+    };
+    position.map(|(_, x, y)| (*x, *y))
+  }
+
+  pub fn owner_at(&self, gameloop: Gameloop) -> u8 {
+    self.owners
+      .iter()
+      .rev()
+      .find(|(owner_gameloop, _)| *owner_gameloop <= gameloop)
+      .or(self.owners.first())
+      .map_or(self.player_id, |(_, player_id)| *player_id)
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectLocation {
+  pub name: String,
+  pub gameloop: Gameloop,
+  pub x: u16,
+  pub y: u16,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BuildEntry {
+  pub name: String,
+  pub started: Gameloop,
+  pub finished: Option<Gameloop>,
+  pub proxy: bool,
+  // supply used when the entry was started
+  pub supply: u16,
+  #[serde(skip)]
+  pub tag_id: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct WorkerDeath {
+  pub name: String,
+  pub gameloop: Gameloop,
+  pub killer_player_id: u8,
+  // None when the killing unit isn't known, e.g. it died in the same gameloop
+  pub killer_name: Option<String>,
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize)]
+pub struct Resources {
+  pub minerals: i64,
+  pub gas: i64,
+}
+
+impl Resources {
+  pub fn total(&self) -> i64 {
+    self.minerals + self.gas
+  }
+
+  pub fn add(&self, other: &Resources) -> Resources {
+    Resources {
+      minerals: self.minerals + other.minerals,
+      gas: self.gas + other.gas,
+    }
+  }
+}
+
+#[derive(Default, Debug, Clone, Copy, Serialize)]
+pub struct ScoreCategories {
+  pub army: Resources,
+  pub economy: Resources,
+  pub technology: Resources,
+}
+
+impl ScoreCategories {
+  pub fn total(&self) -> Resources {
+    self.army.add(&self.economy).add(&self.technology)
+  }
+}
+
+// every value from the m_stats struct of SPlayerStatsEvent
+#[derive(Default, Debug, Clone, Serialize)]
+pub struct Score {
+  pub gameloop: Gameloop,
+  pub current: Resources,
+  pub collection_rate: Resources,
+  pub workers_active: i64,
+  pub used_in_progress: ScoreCategories,
+  pub used_current: ScoreCategories,
+  pub lost: ScoreCategories,
+  pub killed: ScoreCategories,
+  pub friendly_fire: ScoreCategories,
+  pub food_used: f32,
+  pub food_made: f32,
+  pub used_active_forces: Resources,
+}
+
+impl Score {
+  // units in production count towards army value as well as finished units
+  pub fn army_value(&self) -> Resources {
+    self.used_in_progress.army.add(&self.used_current.army)
+  }
+
+  // everything spent plus everything lost plus what hasn't been spent yet
+  pub fn collected(&self) -> Resources {
+    self.current
+      .add(&self.used_in_progress.total())
+      .add(&self.used_current.total())
+      .add(&self.lost.total())
+  }
+}
+
+pub const MAX_SUPPLY: f32 = 200.0;
+
+// supply derived from production starts, births and deaths, which is more granular
+// than the 10 second resolution of player stats snapshots
+#[derive(Default, Debug, Clone)]
+pub struct SupplyTracker {
+  pub used: f32,
+  pub made: f32,
+  // (gameloop, used, made) after every change, made isn't capped at max supply
+  pub changes: Vec<(Gameloop, f32, f32)>,
+}
+
+impl SupplyTracker {
+  // used supply is backdated to when production started, so changes are
+  // inserted in order and added to every later change
+  pub fn update(&mut self, gameloop: Gameloop, used: f32, made: f32) {
+    if used == 0.0 && made == 0.0 {
+      return;
+    }
+
+    self.used += used;
+    self.made += made;
+
+    let index = self.changes.partition_point(|(change_gameloop, _, _)| *change_gameloop <= gameloop);
+    let (used_before, made_before) = match index.checked_sub(1) {
+      Some(previous) => (self.changes[previous].1, self.changes[previous].2),
+      None => (0.0, 0.0),
+    };
+    self.changes.insert(index, (gameloop, used_before + used, made_before + made));
+    for change in &mut self.changes[index + 1..] {
+      change.1 += used;
+      change.2 += made;
+    }
+  }
+
+  pub fn used_at(&self, gameloop: Gameloop) -> f32 {
+    self.changes
+      .iter()
+      .rev()
+      .find(|(change_gameloop, _, _)| *change_gameloop <= gameloop)
+      .map_or(0.0, |(_, used, _)| *used)
+  }
+
+  // (start, end) gameloops where used supply reached supply made below max supply
+  pub fn blocks(&self) -> Vec<(Gameloop, Gameloop)> {
+    let mut blocks = vec![];
+    let mut block_start: Option<Gameloop> = None;
+
+    for (gameloop, used, made) in &self.changes {
+      let blocked = used >= made && *used < MAX_SUPPLY;
+      match (blocked, block_start) {
+        (true, None) => block_start = Some(*gameloop),
+        (false, Some(start)) => {
+          // starting units are born before the town hall at gameloop 0
+          if *gameloop > start {
+            blocks.push((start, *gameloop));
+          }
+          block_start = None;
+        },
+        _other => continue,
+      }
+    }
+
+    if let (Some(start), Some((end, _, _))) = (block_start, self.changes.last()) {
+      if *end > start {
+        blocks.push((start, *end));
+      }
+    }
+
+    blocks
+  }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Expansion {
+  pub name: String,
+  pub started: Gameloop,
+  pub finished: Option<Gameloop>,
+  pub died: Option<Gameloop>,
+  pub x: u16,
+  pub y: u16,
+}
+
+// town halls closer than this to another town hall of the same
+// player are macro hatcheries or in-base command centers
+const MACRO_TOWN_HALL_DISTANCE: f32 = 15.0;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Engagement {
+  pub start: Gameloop,
+  pub end: Gameloop,
+  // average position of the deaths in the engagement
+  pub x: u16,
+  pub y: u16,
+  // deaths, losses and kills are per player
+  pub deaths: Vec<u16>,
+  pub losses: Vec<Resources>,
+  pub killed: Vec<Resources>,
+  // army value of each player from the last snapshot before the engagement
+  pub army_value: Vec<i64>,
+  // winning team, None when no team traded efficiently enough to win
+  pub winner: Option<u8>,
 }
 
+// deaths further apart than this in time or distance are separate engagements
+const ENGAGEMENT_GAP: Gameloop = Gameloop::from_seconds(10);
+const ENGAGEMENT_DISTANCE: f32 = 20.0;
+
+// small skirmishes like a few workers or a scouting unit aren't engagements
+const MIN_ENGAGEMENT_LOSSES: i64 = 400;
+
+// a team has to lose this many times less than every opponent to win
+const ENGAGEMENT_TRADE_RATIO: f32 = 1.25;
+
+// buildings further than this from the main and the natural of the
+// same player are proxies, naturals are usually ~30 cells from the main
+const PROXY_DISTANCE: f32 = 40.0;
+
+// per player fields are indexed by player id - 1
 pub struct Game {
-  pub workers_active: [u8; 2],
-  pub minerals_collected: [u16; 2],
-  pub minerals_lost: [u16; 2],
-  pub gas_collected: [u16; 2],
-  pub gas_lost: [u16; 2],
-  pub collection_rate: Vec<Vec<(u16, u16)>>,
-  pub unspent_resources: Vec<Vec<(u16, u16)>>,
-  pub builds: Vec<Vec<(String, u16)>>,
+  // team of each player, None for ids that aren't players like observers
+  pub teams: Vec<Option<u8>>,
+  pub workers_active: Vec<u8>,
+  pub minerals_collected: Vec<i64>,
+  pub minerals_lost: Vec<i64>,
+  pub gas_collected: Vec<i64>,
+  pub gas_lost: Vec<i64>,
+  pub collection_rate: Vec<Vec<(i64, i64)>>,
+  pub unspent_resources: Vec<Vec<(i64, i64)>>,
+  pub scores: Vec<Vec<Score>>,
+  pub builds: Vec<Vec<BuildEntry>>,
+  pub units: Vec<Vec<BuildEntry>>,
+  pub upgrades: Vec<Vec<(String, Gameloop)>>,
+  pub supply: Vec<SupplyTracker>,
+  // gameloops workers were born on
+  pub worker_births: Vec<Vec<Gameloop>>,
+  // workers lost by each player, drones morphing into buildings aren't included
+  pub worker_deaths: Vec<Vec<WorkerDeath>>,
   pub objects: Vec<GameObject>,
 }
 
 impl Game {
   pub fn new() -> Game {
-    let workers_active: [u8; 2] = [0, 0];
-    let minerals_collected: [u16; 2] = [0, 0];
-    let minerals_lost: [u16; 2] = [0, 0];
-    let gas_collected: [u16; 2] = [0, 0];
-    let gas_lost: [u16; 2] = [0, 0];
-    let collection_rate: Vec<Vec<(u16, u16)>> = vec![vec![], vec![]];
-    let unspent_resources: Vec<Vec<(u16, u16)>> = vec![vec![], vec![]];
-    let builds: Vec<Vec<(String, u16)>> = vec![vec![], vec![]];
-    let objects = vec![];
-
     Game {
-      workers_active,
-      minerals_collected,
-      minerals_lost,
-      gas_collected,
-      gas_lost,
-      collection_rate,
-      unspent_resources,
-      builds,
-      objects,
+      teams: vec![],
+      workers_active: vec![],
+      minerals_collected: vec![],
+      minerals_lost: vec![],
+      gas_collected: vec![],
+      gas_lost: vec![],
+      collection_rate: vec![],
+      unspent_resources: vec![],
+      scores: vec![],
+      builds: vec![],
+      units: vec![],
+      upgrades: vec![],
+      supply: vec![],
+      worker_births: vec![],
+      worker_deaths: vec![],
+      objects: vec![],
     }
   }
 
-  pub fn reset(&mut self) {
-    self.workers_active = [0, 0];
-    self.minerals_collected = [0, 0];
-    self.minerals_lost = [0, 0];
-    self.gas_collected = [0, 0];
-    self.gas_lost = [0, 0];
+  pub fn reset(&mut self, teams: Vec<Option<u8>>) {
+    let player_count = teams.len();
+    self.teams = teams;
 
-    for vec in self.collection_rate.iter_mut() {
-      vec.clear();
+    self.workers_active = vec![0; player_count];
+    self.minerals_collected = vec![0; player_count];
+    self.minerals_lost = vec![0; player_count];
+    self.gas_collected = vec![0; player_count];
+    self.gas_lost = vec![0; player_count];
+    self.collection_rate = vec![vec![]; player_count];
+    self.unspent_resources = vec![vec![]; player_count];
+    self.scores = vec![vec![]; player_count];
+    self.builds = vec![vec![]; player_count];
+    self.units = vec![vec![]; player_count];
+    self.upgrades = vec![vec![]; player_count];
+    self.supply = vec![SupplyTracker::default(); player_count];
+    self.worker_births = vec![vec![]; player_count];
+    self.worker_deaths = vec![vec![]; player_count];
+    self.objects.clear();
+  }
+
+  pub fn find_object(&mut self, tag_id: u32) -> Option<&mut GameObject> {
+    match self.objects.binary_search_by(|obj| obj.tag_id.cmp(&tag_id)) {
+      Ok(idx) => Some(&mut self.objects[idx]),
+      Err(_) => None,
     }
+  }
 
-    for vec in self.unspent_resources.iter_mut() {
-      vec.clear();
+  pub fn object(&self, tag_id: u32) -> Option<&GameObject> {
+    match self.objects.binary_search_by(|obj| obj.tag_id.cmp(&tag_id)) {
+      Ok(idx) => Some(&self.objects[idx]),
+      Err(_) => None,
     }
+  }
+
+  // some events only reference the tag index, which is shared with
+  // dead objects that had the same index before it was recycled
+  pub fn find_live_object(&mut self, tag_index: u32) -> Option<&mut GameObject> {
+    let start = self.objects.partition_point(|obj| obj.tag_index < tag_index);
+    self.objects[start..]
+      .iter_mut()
+      .take_while(|obj| obj.tag_index == tag_index)
+      .find(|obj| obj.died_gameloop.is_none())
+  }
+
+  pub fn building_locations(&self, names: &[String], player_id: u8) -> Vec<ObjectLocation> {
+    self.objects
+      .iter()
+      .filter(|obj|
+        obj.owner_at(obj.init_gameloop) == player_id &&
+        obj.object_type == ObjectType::Building
+      )
+      .filter_map(|obj| obj.positions.first().map(|(_, x, y)| ObjectLocation {
+        name: names[obj.object_name_idx].clone(),
+        gameloop: obj.init_gameloop,
+        x: *x,
+        y: *y,
+      }))
+      .collect()
+  }
+
+  pub fn death_locations(&self, names: &[String], player_id: u8) -> Vec<ObjectLocation> {
+    self.objects
+      .iter()
+      .filter(|obj| obj.player_id == player_id)
+      .filter_map(|obj| match (obj.died_gameloop, obj.positions.last()) {
+        (Some(died_gameloop), Some((_, x, y))) => Some(ObjectLocation {
+          name: names[obj.object_name_idx].clone(),
+          gameloop: died_gameloop,
+          x: *x,
+          y: *y,
+        }),
+        _other => None,
+      })
+      .collect()
+  }
+
+  pub fn engagements(&self, names: &[String]) -> Vec<Engagement> {
+    let player_count = self.teams.len();
+    let team = |player_id: u8| (player_id as usize)
+      .checked_sub(1)
+      .and_then(|player_index| self.teams.get(player_index).copied().flatten());
+
+    // (gameloop, player index, killer index, x, y, cost) of units killed by an opponent
+    let mut deaths: Vec<(Gameloop, usize, usize, u16, u16, Resources)> = self.objects
+      .iter()
+      .filter_map(|obj| {
+        let killer_player_id = obj.killer_player_id?;
+        if team(obj.player_id)? == team(killer_player_id)? {
+          return None;
+        }
+
+        let cost = object_cost(&names[obj.object_name_idx])?;
+        match (obj.died_gameloop, obj.positions.last()) {
+          (Some(died_gameloop), Some((_, x, y))) => Some((
+            died_gameloop,
+            (obj.player_id - 1) as usize,
+            (killer_player_id - 1) as usize,
+            *x,
+            *y,
+            cost,
+          )),
+          _other => None,
+        }
+      })
+      .collect();
+    deaths.sort_by_key(|death| death.0);
+
+    // (engagement, sum of x, sum of y, number of deaths)
+    let mut clusters: Vec<(Engagement, f32, f32, f32)> = vec![];
+    for (gameloop, player_index, killer_index, x, y, cost) in deaths {
+      let cluster = clusters
+        .iter_mut()
+        .filter(|(engagement, _, _, _)| gameloop - engagement.end <= ENGAGEMENT_GAP)
+        .find(|(_, x_sum, y_sum, count)| {
+          let x_distance = x_sum / count - x as f32;
+          let y_distance = y_sum / count - y as f32;
+          (x_distance.powi(2) + y_distance.powi(2)).sqrt() <= ENGAGEMENT_DISTANCE
+        });
+
+      let (engagement, x_sum, y_sum, count) = match cluster {
+        Some(cluster) => cluster,
+        None => {
+          clusters.push((
+            Engagement {
+              start: gameloop,
+              end: gameloop,
+              x,
+              y,
+              deaths: vec![0; player_count],
+              losses: vec![Resources::default(); player_count],
+              killed: vec![Resources::default(); player_count],
+              army_value: vec![0; player_count],
+              winner: None,
+            },
+            0.0,
+            0.0,
+            0.0,
+          ));
+          clusters.last_mut().unwrap()
+        },
+      };
 
-    for vec in self.builds.iter_mut() {
-      vec.clear();
+      engagement.end = gameloop;
+      engagement.deaths[player_index] += 1;
+      *x_sum += x as f32;
+      *y_sum += y as f32;
+      *count += 1.0;
+      engagement.x = (*x_sum / *count) as u16;
+      engagement.y = (*y_sum / *count) as u16;
+      engagement.losses[player_index] = engagement.losses[player_index].add(&cost);
+      engagement.killed[killer_index] = engagement.killed[killer_index].add(&cost);
     }
 
-    self.objects.clear();
+    clusters
+      .into_iter()
+      .map(|(engagement, _, _, _)| engagement)
+      .filter(|engagement| engagement.losses.iter().map(|losses| losses.total()).sum::<i64>() >= MIN_ENGAGEMENT_LOSSES)
+      .map(|mut engagement| {
+        for player_index in 0..player_count {
+          engagement.army_value[player_index] = self.scores[player_index]
+            .iter()
+            .rev()
+            .find(|score| score.gameloop <= engagement.start)
+            .map_or(0, |score| score.army_value().total());
+        }
+
+        // (team, losses) of teams that lost or killed something
+        let mut team_losses: Vec<(u8, f32)> = vec![];
+        for (player_index, player_team) in self.teams.iter().enumerate() {
+          let player_team = match player_team {
+            Some(player_team) => *player_team,
+            None => continue,
+          };
+          let losses = engagement.losses[player_index].total();
+          if losses == 0 && engagement.killed[player_index].total() == 0 {
+            continue;
+          }
+
+          match team_losses.iter_mut().find(|(team, _)| *team == player_team) {
+            Some((_, team_loss)) => *team_loss += losses as f32,
+            None => team_losses.push((player_team, losses as f32)),
+          }
+        }
+
+        engagement.winner = team_losses
+          .iter()
+          .find(|(team, losses)| team_losses
+            .iter()
+            .filter(|(other_team, _)| other_team != team)
+            .all(|(_, other_losses)| *other_losses >= losses * ENGAGEMENT_TRADE_RATIO)
+          )
+          .map(|(team, _)| *team);
+
+        engagement
+      })
+      .collect()
   }
+
+  // town halls at new locations in the order they were started, including the
+  // starting town hall. bases that were lost and retaken are only included once
+  pub fn expansions(&self, names: &[String], player_id: u8) -> Vec<Expansion> {
+    let mut expansions: Vec<Expansion> = vec![];
+    for base in self.bases(names, player_id) {
+      if !expansions.iter().any(|expansion| is_same_base(expansion, base.x, base.y)) {
+        expansions.push(base);
+      }
+    }
+
+    expansions
+  }
+
+  // town halls that weren't next to another live town hall when they were
+  // started, including rebuilt bases. cancelled town halls aren't included
+  fn bases(&self, names: &[String], player_id: u8) -> Vec<Expansion> {
+    let mut town_halls: Vec<&GameObject> = self.objects
+      .iter()
+      .filter(|obj|
+        obj.owner_at(obj.init_gameloop) == player_id &&
+        TOWN_HALLS.contains(&names[obj.object_name_idx].as_str()) &&
+        !(obj.done_gameloop.is_none() && obj.died_gameloop.is_some())
+      )
+      .collect();
+    town_halls.sort_by_key(|town_hall| town_hall.init_gameloop);
+
+    let mut bases: Vec<Expansion> = vec![];
+    for town_hall in town_halls {
+      let (x, y) = match town_hall.base_position(names) {
+        Some(position) => position,
+        None => continue,
+      };
+
+      // bases that were destroyed can be rebuilt in the same location
+      let is_macro = bases
+        .iter()
+        .filter(|base| base.died.is_none_or(|died| died > town_hall.init_gameloop))
+        .any(|base| is_same_base(base, x, y));
+
+      if !is_macro {
+        bases.push(Expansion {
+          name: names[town_hall.object_name_idx].clone(),
+          started: town_hall.init_gameloop,
+          finished: town_hall.done_gameloop,
+          died: town_hall.died_gameloop,
+          x,
+          y,
+        });
+      }
+    }
+
+    bases
+  }
+
+  // (gameloop, bases) for every finished or destroyed base
+  pub fn base_counts(&self, names: &[String], player_id: u8) -> Vec<(Gameloop, u8)> {
+    let mut changes: Vec<(Gameloop, i8)> = vec![];
+    for expansion in self.bases(names, player_id) {
+      if let Some(finished) = expansion.finished {
+        changes.push((finished, 1));
+      }
+
+      if let Some(died) = expansion.died {
+        changes.push((died, -1));
+      }
+    }
+    changes.sort_by_key(|change| change.0);
+
+    let mut bases: u8 = 0;
+    changes
+      .into_iter()
+      .map(|(gameloop, change)| {
+        bases = (bases as i8 + change).max(0) as u8;
+        (gameloop, bases)
+      })
+      .collect()
+  }
+
+  // first town hall of the player, which is present from the start of the game
+  pub fn start_location(&self, names: &[String], player_id: u8) -> Option<(u16, u16)> {
+    self.base_locations(names, player_id)
+      .iter()
+      .min_by_key(|location| location.gameloop)
+      .map(|location| (location.x, location.y))
+  }
+
+  // the natural is the expansion closest to the starting town hall, even if
+  // it was taken after the building was started
+  pub fn is_proxy(&self, names: &[String], player_id: u8, position: (u16, u16)) -> bool {
+    let start_location = match self.start_location(names, player_id) {
+      Some(start_location) => start_location,
+      // can't tell where the player's bases are
+      None => return false,
+    };
+    let natural = self.expansions(names, player_id)
+      .into_iter()
+      .map(|expansion| (expansion.x, expansion.y))
+      .filter(|location| *location != start_location)
+      .min_by(|a, b| distance(start_location, *a).total_cmp(&distance(start_location, *b)));
+
+    [Some(start_location), natural]
+      .iter()
+      .flatten()
+      .all(|town_hall| distance(*town_hall, position) > PROXY_DISTANCE)
+  }
+
+  // builds are recorded before later town halls exist, so proxies are
+  // marked once every event has been parsed. town halls away from the
+  // main and natural are expansions, not proxies
+  pub fn mark_proxies(&mut self, names: &[String]) {
+    for player_index in 0..self.builds.len() {
+      let player_id = player_index as u8 + 1;
+      let proxies: Vec<bool> = self.builds[player_index]
+        .iter()
+        .map(|build_entry| match self.object(build_entry.tag_id).and_then(|obj| obj.positions.first()) {
+          Some(_) if TOWN_HALLS.contains(&build_entry.name.as_str()) => false,
+          Some((_, x, y)) => self.is_proxy(names, player_id, (*x, *y)),
+          None => false,
+        })
+        .collect();
+
+      for (build_entry, proxy) in self.builds[player_index].iter_mut().zip(proxies) {
+        build_entry.proxy = proxy;
+      }
+    }
+  }
+
+  pub fn base_locations(&self, names: &[String], player_id: u8) -> Vec<ObjectLocation> {
+    self.building_locations(names, player_id)
+      .into_iter()
+      .filter(|location| TOWN_HALLS.contains(&location.name.as_str()))
+      .collect()
+  }
+}
+
+fn is_same_base(base: &Expansion, x: u16, y: u16) -> bool {
+  distance((base.x, base.y), (x, y)) < MACRO_TOWN_HALL_DISTANCE
+}
+
+fn distance(a: (u16, u16), b: (u16, u16)) -> f32 {
+  let x_distance = a.0 as f32 - b.0 as f32;
+  let y_distance = a.1 as f32 - b.1 as f32;
+  (x_distance.powi(2) + y_distance.powi(2)).sqrt()
 }
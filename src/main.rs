@@ -3,20 +3,30 @@ mod protocol;
 mod mpq;
 mod replay;
 mod events;
+mod analysis;
 mod utils;
 mod game;
 mod parser;
 mod builds;
 mod cluster;
+mod time;
+mod result;
+mod normalize;
 
 use crate::parser::{ReplayParser, ReplaySummary};
+use crate::protocol::EventFilter;
+use crate::analysis::mechanics::MECHANICS_EVENTS;
+use crate::analysis::ending::{GameEnding, ENDING_EVENTS};
 use crate::replay::Replay;
 use crate::utils::visit_dirs;
 use crate::builds::Builds;
+use crate::analysis::abilities::Abilities;
+use crate::time::Gameloop;
 
 use serde::Serialize;
 use std::collections::HashSet;
-use std::fs::File;
+use std::env;
+use std::fs::{read_to_string, File};
 use std::path::Path;
 use csv::Writer;
 
@@ -33,7 +43,12 @@ pub enum SummaryStat {
 pub struct Player {
   id: u8,
   name: String,
+  // race played
   race: String,
+  // race picked in the lobby, Random for random players. None when
+  // neither metadata nor lobby slots have it
+  selected_race: Option<String>,
+  team: u8,
 }
 
 #[derive(Serialize)]
@@ -44,19 +59,61 @@ struct SerializedReplays {
 #[derive(Clone, Serialize)]
 pub struct TinybirdGame {
   content_hash: String,
+  // win, tie or unknown. winner and loser are the first player
+  // of each team when there's no winner
+  result: String,
+  // metadata, details, leave_events or none
+  result_source: String,
   winner_id: u8,
   winner_name: String,
   winner_race: String,
+  winner_selected_race: Option<String>,
   winner_build: String,
+  winner_upgrades: String,
   loser_id: u8,
   loser_name: String,
   loser_race: String,
+  loser_selected_race: Option<String>,
   loser_build: String,
+  loser_upgrades: String,
+  // gameloops the 2nd, 3rd and 4th bases were started
+  winner_second_base: Option<Gameloop>,
+  winner_third_base: Option<Gameloop>,
+  winner_fourth_base: Option<Gameloop>,
+  loser_second_base: Option<Gameloop>,
+  loser_third_base: Option<Gameloop>,
+  loser_fourth_base: Option<Gameloop>,
+  // 0 when game events weren't decoded, apm falls back to metadata
+  winner_apm: u16,
+  winner_epm: u16,
+  winner_spm: u16,
+  loser_apm: u16,
+  loser_epm: u16,
+  loser_spm: u16,
+  // empty unless game events were decoded with an ability table naming
+  // injects, chrono boosts and calldowns
+  winner_inject_uptime: Option<f32>,
+  winner_chrono_boosts: Option<u16>,
+  winner_mules: u16,
+  winner_energy_efficiency: Option<f32>,
+  winner_idle_production: u16,
+  loser_inject_uptime: Option<f32>,
+  loser_chrono_boosts: Option<u16>,
+  loser_mules: u16,
+  loser_energy_efficiency: Option<f32>,
+  loser_idle_production: u16,
+  // gg_leave, disconnect, elimination, time_out or unknown when leave events weren't decoded
+  ending: String,
+  first_leaver_id: Option<u8>,
+  first_leave_gameloop: Option<Gameloop>,
   matchup: String,
   players: String,
   player_names: String,
   builds: String,
+  build_times: String,
   map: String,
+  // canonical map id shared by every title of the map
+  map_id: String,
   game_length: u16,
   played_at: u64,
   event: String,
@@ -66,25 +123,35 @@ pub struct TinybirdGame {
 #[derive(Default, Debug, Clone, Serialize)]
 pub struct TinybirdTimelineEntry {
   content_hash: String,
-  gameloop: u16,
+  gameloop: Gameloop,
   // win: bool,
   win: u8,
+  #[serde(skip)]
+  player_team: u8,
   player: String,
   player_race: String,
   // player_build: String,
-  player_collection_rate: u16,
-  player_army_value: u16,
+  player_collection_rate: i64,
+  player_army_value: i64,
   player_workers_active: u16,
-  // player_workers_lost: u16,
-  // player_workers_killed: u16,
+  player_supply_used: u16,
+  player_supply_made: u16,
+  player_resources_lost: i64,
+  player_resources_killed: i64,
+  player_workers_lost: u16,
+  player_workers_killed: u16,
   opponent: String,
   opponent_race: String,
   // opponent_build: String,
-  opponent_collection_rate: u16,
-  opponent_army_value: u16,
+  opponent_collection_rate: i64,
+  opponent_army_value: i64,
   opponent_workers_active: u16,
-  // opponent_workers_lost: u16,
-  // opponent_workers_killed: u16,
+  opponent_supply_used: u16,
+  opponent_supply_made: u16,
+  opponent_resources_lost: i64,
+  opponent_resources_killed: i64,
+  opponent_workers_lost: u16,
+  opponent_workers_killed: u16,
   matchup: String,
   map: String,
   event: String,
@@ -93,6 +160,9 @@ pub struct TinybirdTimelineEntry {
   game_version: String,
 }
 
+// disconnects before this are too early for builds to have decided the game
+const EARLY_DISCONNECT: Gameloop = Gameloop::from_seconds(300);
+
 fn main() {
   let now = Instant::now();
 
@@ -100,23 +170,73 @@ fn main() {
   let replay_dir = Path::new("/Users/lukeholroyd/Desktop/replays/structured/");
   let mut replays: Vec<Replay> = vec![];
   let mut seen_replays: HashSet<String> = HashSet::new();
-  visit_dirs(&mut replays, replay_dir).unwrap();
+
+  // extra tracker events can be enabled with: --events SUpgradeEvent,SUnitDoneEvent
+  let mut event_filter = EventFilter::new();
+  let args: Vec<String> = env::args().collect();
+  if let Some(events_index) = args.iter().position(|arg| arg == "--events") {
+    if let Some(events) = args.get(events_index + 1) {
+      for event in events.split(',') {
+        if !event_filter.insert(event) {
+          println!("unknown event {:?}", event);
+        }
+      }
+    }
+  }
+
+  // apm, epm and spm need game events which are slow to decode: --mechanics
+  if args.iter().any(|arg| arg == "--mechanics") {
+    for event in MECHANICS_EVENTS {
+      event_filter.insert(event);
+    }
+  }
+
+  // how games ended needs leave, join and reconnect events: --endings
+  if args.iter().any(|arg| arg == "--endings") {
+    for event in ENDING_EVENTS {
+      event_filter.insert(event);
+    }
+  }
+
+  // random players' builds skew matchup stats: --exclude-random
+  let exclude_random = args.iter().any(|arg| arg == "--exclude-random");
+
+  visit_dirs(&mut replays, replay_dir, &event_filter).unwrap();
 
   let num_replays = replays.len();
   println!("visited {:?} files in {:.2?}", num_replays, now.elapsed());
 
   let replay_summaries: Vec<ReplaySummary> = vec![];
   let mut replay_builds: Vec<String> = vec![];
-  // let mut replay_units: Vec<String> = vec![];
+  let mut replay_units: Vec<String> = vec![];
   let mut result = SerializedReplays {
     replays: replay_summaries,
   };
 
   let mut tinybird_serialized: Vec<TinybirdGame> = vec![];
-  // let mut tinybird_timelines: Vec<TinybirdTimelineEntry> = vec![];
+  let mut tinybird_timelines: Vec<TinybirdTimelineEntry> = vec![];
 
   let mut replay_parser = ReplayParser::new();
 
+  // replays from other builds need their own ability table: --abilities abilities.csv
+  if let Some(abilities_index) = args.iter().position(|arg| arg == "--abilities") {
+    if let Some(path) = args.get(abilities_index + 1) {
+      let contents = read_to_string(path).expect("Couldn't read abilities file");
+      replay_parser.abilities = Abilities::from_csv(&contents).expect("Couldn't parse abilities file");
+    }
+  }
+  if !replay_parser.abilities.knows("SpawnLarva") {
+    println!("ability table doesn't name macro abilities, injects, chrono boosts and calldowns won't be counted");
+  }
+
+  // extra map names and aliases, e.g. localized titles of older pools, on top of the embedded table: --maps maps.csv
+  if let Some(maps_index) = args.iter().position(|arg| arg == "--maps") {
+    if let Some(path) = args.get(maps_index + 1) {
+      let contents = read_to_string(path).expect("Couldn't read maps file");
+      replay_parser.maps.extend_csv(&contents).expect("Couldn't parse maps file");
+    }
+  }
+
   let mut build_tokens = Builds::new();
 
   for replay in replays {
@@ -125,16 +245,18 @@ fn main() {
     if seen_replays.contains(&content_hash) {
       continue;
     }
+    let file_path = replay.file_path.clone();
 
     // refactor event and replay parsers into single parser
     let replay_summary = match replay_parser.parse_replay(
       replay,
       &mut replay_builds,
-      // &mut replay_units,
+      &mut replay_units,
     ) {
       Ok(summary) => summary,
       Err(e) => {
         // panic!("Error parsing replay: {e}");
+        println!("skipping replay {:?}: {e}", file_path);
         continue;
       },
     };
@@ -143,75 +265,50 @@ fn main() {
   }
 
   for replay_summary in result.replays {
-    if &replay_summary.tinybird.winner_build != "" && &replay_summary.tinybird.loser_build != "" {
+    // ties and unknown results don't have a winner or loser
+    if
+      replay_summary.winning_team.is_some() &&
+      !replay_summary.tinybird.winner_build.is_empty() &&
+      !replay_summary.tinybird.loser_build.is_empty()
+    {
       tinybird_serialized.push(replay_summary.tinybird.clone());
     }
-    // tinybird_timelines.extend(replay_summary.timeline.clone());
+    tinybird_timelines.extend(replay_summary.timeline.clone());
 
-    let mut races = vec![];
-    let mut matchup = vec![];
-    for player in &replay_summary.players {
-      races.push(player.race.clone());
-      matchup.push(player.race.clone());
+    // ties and unknown results can't be counted as wins or losses
+    let winning_team = match replay_summary.winning_team {
+      Some(team) => team,
+      None => continue,
+    };
+    // early disconnects aren't decided by builds
+    let early_disconnect = replay_summary.ending.ending == GameEnding::Disconnect &&
+      replay_summary.ending.first_leave_gameloop.is_some_and(|gameloop| gameloop < EARLY_DISCONNECT);
+    if early_disconnect {
+      continue;
     }
-    matchup.sort();
+    let matchup_prefix = &replay_summary.matchup;
 
-    let matchup_prefix = matchup.join(",");
+    for player in &replay_summary.players {
+      if exclude_random && player.selected_race.as_deref() == Some("Random") {
+        continue;
+      }
 
-    for (p_id, player_build_index) in replay_summary.build_mappings.iter().enumerate() {
-      let player_build = replay_builds[*player_build_index as usize].split(",").map(|s| s.to_string()).collect();
-      let token_prefix = format!("{}-{}", races[p_id], matchup_prefix);
+      let p_id = (player.id - 1) as usize;
+      let token_prefix = format!("{}-{}", player.race, matchup_prefix);
+      let win = player.team == winning_team;
 
-      let win = (p_id + 1) == replay_summary.winner as usize;
-      build_tokens.generate_tokens(&player_build, win, token_prefix);
-    }
+      let player_build_index = replay_summary.build_mappings[p_id];
+      let player_build: Vec<String> = replay_builds[player_build_index as usize].split(",").map(|s| s.to_string()).collect();
+      build_tokens.generate_tokens(&player_build, win, token_prefix.clone());
 
-    // let matchup_prefix = matchup.join(",");
-    // for (p_id, player_unit_index) in replay_summary.unit_mappings.iter().enumerate() {
-    //   let player_units: Vec<String> = replay_units[*player_unit_index as usize].split(",").map(|s| s.to_string()).collect();
-    //   let token_prefix = format!("{}-{}", races[p_id], matchup_prefix);
-
-    //   let mut win = false;
-    //   win = (p_id + 1) == replay_summary.winner as usize;
-
-    //   build_tokens.units
-    //     .entry(format!(
-    //       "{token_prefix}__{}",
-    //       player_units.join(",")
-    //     ))
-    //     .and_modify(|units_count| {
-    //       units_count.total += 1;
-    //       if win {
-    //         units_count.wins += 1;
-    //       } else {
-    //         units_count.losses += 1;
-    //       }
-    //     })
-    //     .or_insert_with(|| {
-    //       let mut units = BuildCount {
-    //         total: 1,
-    //         wins: 0,
-    //         losses: 0,
-    //       };
-  
-    //       if win {
-    //         units.wins += 1;
-    //       } else {
-    //         units.losses += 1;
-    //       }
-  
-    //       units
-    //     });
-
-    //   if player_units.len() <= 3 {
-    //     // println!("Build has less than 3 buildings: {:?}", player_build);
-    //     continue;
-    //   }
-    // }
+      let player_unit_index = replay_summary.unit_mappings[p_id];
+      let player_units: Vec<String> = replay_units[player_unit_index as usize].split(",").map(|s| s.to_string()).collect();
+      build_tokens.generate_unit_tokens(&player_units, win, token_prefix);
+    }
   }
 
   build_tokens.generate_matchup_build_trees();
-  // build_tokens.generate_matchup_unit_trees();
+  build_tokens.generate_matchup_unit_trees();
 
   println!("{:?} replays parsed in {:.2?}, {:?} per replay", num_replays, now.elapsed(), now.elapsed() / num_replays as u32);
 
@@ -223,16 +320,16 @@ fn main() {
   // serde_json::to_writer(&replay_output, &mapped_replays);
 
   let build_output = File::create("generated/builds.json").unwrap();
-  serde_json::to_writer(&build_output, &build_tokens.builds);
+  serde_json::to_writer(&build_output, &build_tokens.builds).unwrap();
 
   let raw_build_tree_output = File::create("generated/raw_build_tree.json").unwrap();
-  serde_json::to_writer(&raw_build_tree_output, &build_tokens.raw_build_tree);
+  serde_json::to_writer(&raw_build_tree_output, &build_tokens.raw_build_tree).unwrap();
 
-  // let raw_unit_tree_output = File::create("generated/raw_unit_tree.json").unwrap();
-  // serde_json::to_writer(&raw_unit_tree_output, &build_tokens.raw_unit_tree);
+  let unit_output = File::create("generated/units.json").unwrap();
+  serde_json::to_writer(&unit_output, &build_tokens.units).unwrap();
 
-  let build_token_output = File::create("generated/tokens.json").unwrap();
-  serde_json::to_writer(&build_token_output, &build_tokens.build_token_path_mappings);
+  let raw_unit_tree_output = File::create("generated/raw_unit_tree.json").unwrap();
+  serde_json::to_writer(&raw_unit_tree_output, &build_tokens.raw_unit_tree).unwrap();
 
   File::create("tinybird_sc2.csv").unwrap();
   let mut wtr = Writer::from_path("tinybird_sc2.csv").unwrap();
@@ -241,12 +338,12 @@ fn main() {
   }
   wtr.flush().unwrap();
 
-  // File::create("tinybird_sc2_timelines.csv").unwrap();
-  // let mut wtr = Writer::from_path("tinybird_sc2_timelines.csv").unwrap();
-  // for record in &tinybird_timelines {
-  //   wtr.serialize(record).unwrap();
-  // }
-  // wtr.flush().unwrap();
+  File::create("tinybird_sc2_timelines.csv").unwrap();
+  let mut wtr = Writer::from_path("tinybird_sc2_timelines.csv").unwrap();
+  for record in &tinybird_timelines {
+    wtr.serialize(record).unwrap();
+  }
+  wtr.flush().unwrap();
 
   println!("replays serialized in {:?}", now.elapsed());
 }
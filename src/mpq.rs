@@ -4,19 +4,14 @@ use std::io::copy;
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::io::SeekFrom;
-use std::path::PathBuf;
 
 // use bzip2::Decompress;
 // use bzip2_rs::decoder::Decoder;
 use bzip2_rs::DecoderReader;
 
-const MPQ_FILE_IMPLODE: u32 = 0x00000100;
 const MPQ_FILE_COMPRESS: u32 = 0x00000200;
 const MPQ_FILE_ENCRYPTED: u32 = 0x00010000;
-const MPQ_FILE_FIX_KEY: u32 = 0x00020000;
 const MPQ_FILE_SINGLE_UNIT: u32 = 0x01000000;
-const MPQ_FILE_DELETE_MARKER: u32 = 0x02000000;
-const MPQ_FILE_SECTOR_CRC: u32 = 0x04000000;
 const MPQ_FILE_EXISTS: u32 = 0x80000000;
 
 const MPQ_MAGIC_A: [u8; 4] = [77, 80, 81, 26];
@@ -24,42 +19,24 @@ const MPQ_MAGIC_B: [u8; 4] = [77, 80, 81, 27];
 
 #[derive(Copy, Clone)]
 enum MPQHash {
-  TableOffset = 0,
   HashA = 1,
   HashB = 2,
   Table = 3,
 }
 
 pub struct MPQFileHeader {
-  magic: [u8; 4],
   offset: u32,
-  header_size: u32,
-  archive_size: u32,
-  format_version: u16,
-  sector_size_shift: u16,
   hash_table_offset: u32,
   block_table_offset: u32,
   hash_table_entries: u32,
   block_table_entries: u32,
   pub user_data_header: Option<MPQUserDataHeader>,
-  extended: Option<MPQFileHeaderExt>,
 }
 
 // MPQFileHeader.struct_format = '< 4s 2I 2H 4I' = 4 + 8 + 4 + 16 = 32 bytes
 
-struct MPQFileHeaderExt {
-  extended_block_table_offset: i64,
-  hash_table_offset_high: i16,
-  block_table_offset_high: i16,
-}
-
-// MPQFileHeaderExt.struct_format = 'q 2h'
-
 pub struct MPQUserDataHeader {
-  magic: [u8; 4],
-  user_data_size: u32,
   mpq_header_offset: u32,
-  user_data_header_size: u32,
   pub content: Vec<u8>,
 }
 
@@ -69,8 +46,6 @@ pub struct MPQUserDataHeader {
 struct HashTableEntry {
   hash_a: u32,
   hash_b: u32,
-  locale: u16,
-  platform: u16,
   block_table_index: u32,
 }
 
@@ -98,20 +73,18 @@ pub struct MPQArchive {
   hash_table: Vec<MPQTableEntry>,
   block_table: Vec<MPQTableEntry>,
   encryption_table: HashMap<u64, u64>,
-  compressed: Vec<u8>,
-  decompressed_offsets: Vec<usize>,
-  compression_type: u8,
 }
 
 impl MPQArchive {
-  pub fn new(filename: &str) -> MPQArchive {
-    let file = File::open(filename).expect("Failed to read replay file");
+  // fails for files that aren't MPQ archives or are truncated before their tables end
+  pub fn new(filename: &str) -> Result<MPQArchive, &'static str> {
+    let file = File::open(filename).map_err(|_| "Failed to read replay file")?;
     let mut reader = BufReader::new(file);
-    let header = MPQArchive::read_header(&mut reader);
+    let header = MPQArchive::read_header(&mut reader)?;
 
     let encryption_table = MPQArchive::prepare_encryption_table();
-    let hash_table = MPQArchive::read_table(&mut reader, &header, &encryption_table, "hash");
-    let block_table = MPQArchive::read_table(&mut reader, &header, &encryption_table, "block");
+    let hash_table = MPQArchive::read_table(&mut reader, &header, &encryption_table, "hash")?;
+    let block_table = MPQArchive::read_table(&mut reader, &header, &encryption_table, "block")?;
     // let block_table_entry = MPQArchive::read_block_entry(
     //   "(listfile)",
     //   &encryption_table,
@@ -119,46 +92,35 @@ impl MPQArchive {
     //   &block_table,
     // ).expect("Couldn't find block table entry");
     // let contents = MPQArchive::_read_file(&mut reader, &header, &block_table_entry, false);
-    let compressed = vec![];
-    let decompressed_offsets = vec![];
-    let compression_type = 0;
 
-    MPQArchive {
+    Ok(MPQArchive {
       file: reader,
       header,
       hash_table,
       block_table,
       encryption_table,
-      compressed,
-      decompressed_offsets,
-      compression_type,
-    }
+    })
   }
 
-  fn read_header(file: &mut BufReader<File>) -> MPQFileHeader {
+  fn read_header(file: &mut BufReader<File>) -> Result<MPQFileHeader, &'static str> {
     let mut magic = [0; 4];
-    file.read_exact(&mut magic).unwrap();
-    file.seek(SeekFrom::Start(0));
+    file.read_exact(&mut magic).map_err(|_| "Truncated file header")?;
+    file.rewind().map_err(|_| "Failed to seek")?;
 
     match magic {
-      MPQ_MAGIC_A => MPQArchive::read_mpq_header(magic, file, None),
+      MPQ_MAGIC_A => MPQArchive::read_mpq_header(file, None),
       MPQ_MAGIC_B => {
-        let user_data_header = MPQArchive::read_mpq_user_data_header(magic, file);
-        MPQArchive::read_mpq_header(magic, file, Some(user_data_header))
+        let user_data_header = MPQArchive::read_mpq_user_data_header(file)?;
+        MPQArchive::read_mpq_header(file, Some(user_data_header))
       }
-      _other => panic!("Invalid file header"),
+      _other => Err("Invalid file header"),
     }
   }
 
   fn read_mpq_header(
-    magic: [u8; 4],
     file: &mut BufReader<File>,
     user_data_header: Option<MPQUserDataHeader>,
-  ) -> MPQFileHeader {
-    let mut header_size = [0; 4];
-    let mut archive_size = [0; 4];
-    let mut format_version = [0; 2];
-    let mut sector_size_shift = [0; 2];
+  ) -> Result<MPQFileHeader, &'static str> {
     let mut hash_table_offset = [0; 4];
     let mut block_table_offset = [0; 4];
     let mut hash_table_entries = [0; 4];
@@ -168,75 +130,43 @@ impl MPQArchive {
       Some(header) => header.mpq_header_offset,
       None => 0,
     };
-    file.seek(SeekFrom::Start(offset as u64 + 4))
-      .expect("Failed to seek");
-
-    file.read_exact(&mut header_size).unwrap();
-    file.read_exact(&mut archive_size).unwrap();
-    file.read_exact(&mut format_version).unwrap();
-    file.read_exact(&mut sector_size_shift).unwrap();
-    file.read_exact(&mut hash_table_offset).unwrap();
-    file.read_exact(&mut block_table_offset).unwrap();
-    file.read_exact(&mut hash_table_entries).unwrap();
-    file.read_exact(&mut block_table_entries).unwrap();
-
-    let mut header_extension = None;
-    let format_version_value = u16::from_le_bytes(format_version);
-
-    if format_version_value == 1 {
-      let mut extended_block_table_offset = [0; 8];
-      let mut hash_table_offset_high = [0; 2];
-      let mut block_table_offset_high = [0; 2];
-
-      file.read_exact(&mut extended_block_table_offset).unwrap();
-      file.read_exact(&mut hash_table_offset_high).unwrap();
-      file.read_exact(&mut block_table_offset_high).unwrap();
-
-      header_extension = Some(MPQFileHeaderExt {
-        extended_block_table_offset: i64::from_ne_bytes(extended_block_table_offset),
-        hash_table_offset_high: i16::from_ne_bytes(hash_table_offset_high),
-        block_table_offset_high: i16::from_ne_bytes(block_table_offset_high),
-      });
-    }
+    // skips the magic, header size, archive size, format version and sector size shift
+    file.seek(SeekFrom::Start(offset as u64 + 16))
+      .map_err(|_| "Failed to seek")?;
 
-    MPQFileHeader {
-      magic,
+    file.read_exact(&mut hash_table_offset).map_err(|_| "Truncated archive header")?;
+    file.read_exact(&mut block_table_offset).map_err(|_| "Truncated archive header")?;
+    file.read_exact(&mut hash_table_entries).map_err(|_| "Truncated archive header")?;
+    file.read_exact(&mut block_table_entries).map_err(|_| "Truncated archive header")?;
+
+    Ok(MPQFileHeader {
       offset,
-      header_size: u32::from_le_bytes(header_size),
-      archive_size: u32::from_le_bytes(archive_size),
-      format_version: format_version_value,
-      sector_size_shift: u16::from_le_bytes(sector_size_shift),
       hash_table_offset: u32::from_le_bytes(hash_table_offset),
       block_table_offset: u32::from_le_bytes(block_table_offset),
       hash_table_entries: u32::from_le_bytes(hash_table_entries),
       block_table_entries: u32::from_le_bytes(block_table_entries),
       user_data_header,
-      extended: header_extension,
-    }
+    })
   }
 
-  fn read_mpq_user_data_header(magic: [u8; 4], file: &mut BufReader<File>) -> MPQUserDataHeader {
+  fn read_mpq_user_data_header(file: &mut BufReader<File>) -> Result<MPQUserDataHeader, &'static str> {
     let mut user_data_size = [0; 4];
     let mut mpq_header_offset = [0; 4];
     let mut user_data_header_size = [0; 4];
 
-    file.seek(SeekFrom::Start(4));
+    file.seek(SeekFrom::Start(4)).map_err(|_| "Failed to seek")?;
 
-    file.read_exact(&mut user_data_size).unwrap();
-    file.read_exact(&mut mpq_header_offset).unwrap();
-    file.read_exact(&mut user_data_header_size).unwrap();
+    file.read_exact(&mut user_data_size).map_err(|_| "Truncated user data header")?;
+    file.read_exact(&mut mpq_header_offset).map_err(|_| "Truncated user data header")?;
+    file.read_exact(&mut user_data_header_size).map_err(|_| "Truncated user data header")?;
 
-    let user_data_header_size_value = u32::from_le_bytes(user_data_header_size);
-    let mut content = vec![0; user_data_header_size_value as usize];
-    file.read_exact(&mut content).unwrap();
+    let mut content = vec![0; u32::from_le_bytes(user_data_header_size) as usize];
+    file.read_exact(&mut content).map_err(|_| "Truncated user data header")?;
 
-    MPQUserDataHeader {
-      magic,
-      user_data_size: u32::from_le_bytes(user_data_size),
+    Ok(MPQUserDataHeader {
       mpq_header_offset: u32::from_le_bytes(mpq_header_offset),
-      user_data_header_size: user_data_header_size_value,
       content,
-    }
+    })
   }
 
   fn read_table(
@@ -244,7 +174,7 @@ impl MPQArchive {
     header: &MPQFileHeader,
     table: &HashMap<u64, u64>,
     table_entry_type: &str,
-  ) -> Vec<MPQTableEntry> {
+  ) -> Result<Vec<MPQTableEntry>, &'static str> {
     let (table_offset, table_entries, key) = match table_entry_type {
       "hash" => (
         header.hash_table_offset,
@@ -260,10 +190,10 @@ impl MPQArchive {
     };
 
     let file_offset: u32 = table_offset + header.offset;
-    file.seek(SeekFrom::Start(file_offset as u64));
+    file.seek(SeekFrom::Start(file_offset as u64)).map_err(|_| "Failed to seek")?;
 
     let mut data = vec![0; (table_entries * 16) as usize];
-    file.read_exact(&mut data).unwrap();
+    file.read_exact(&mut data).map_err(|_| "Truncated archive table")?;
     let decrypted_data = MPQArchive::decrypt(table, &data, key);
 
     let mut table_values = Vec::with_capacity(table_entries as usize);
@@ -276,16 +206,13 @@ impl MPQArchive {
         "hash" => {
           let hash_a = u32::from_le_bytes(table_entry[0..4].try_into().unwrap());
           let hash_b = u32::from_le_bytes(table_entry[4..8].try_into().unwrap());
-          let locale = u16::from_le_bytes(table_entry[8..10].try_into().unwrap());
-          let platform = u16::from_le_bytes(table_entry[10..12].try_into().unwrap());
+          // bytes 8..12 are the locale and platform
           let block_table_index =
             u32::from_le_bytes(table_entry[12..16].try_into().unwrap());
 
           MPQTableEntry::Hash(HashTableEntry {
             hash_a,
             hash_b,
-            locale,
-            platform,
             block_table_index,
           })
         }
@@ -308,7 +235,7 @@ impl MPQArchive {
       table_values.push(entry_value);
     }
 
-    table_values
+    Ok(table_values)
   }
 
   fn prepare_encryption_table() -> HashMap<u64, u64> {
@@ -352,7 +279,7 @@ impl MPQArchive {
     let mut result = vec![];
 
     for i in 0..(data.len() / 4) {
-      seed2 += table[&(0x400 + (seed1 & 0xFF))] as u64;
+      seed2 += table[&(0x400 + (seed1 & 0xFF))];
       seed2 &= 0xFFFFFFFF;
 
       let position = i * 4;
@@ -383,10 +310,11 @@ impl MPQArchive {
       }
 
       let offset = block_entry.offset + header.offset;
-      file.seek(SeekFrom::Start(offset as u64));
+      // files past the end of truncated archives are missing
+      file.seek(SeekFrom::Start(offset as u64)).ok()?;
 
-      let mut file_data = vec![0; block_entry.archived_size as usize];
-      file.read_exact(&mut file_data).unwrap();
+      let mut file_data = vec![0; block_entry.archived_size];
+      file.read_exact(&mut file_data).ok()?;
 
       if block_entry.flags & MPQ_FILE_ENCRYPTED != 0 {
         panic!("Encrpytion not supported");
@@ -411,10 +339,10 @@ impl MPQArchive {
         // for i in 0..(positions.len() - (crc ? 2 : 1)) {
         //   let sector = file_data[positions[i]..positions[i + 1]]
         // }
-      } else if (block_entry.flags & MPQ_FILE_COMPRESS != 0
-        && (force_decompress || block_entry.size > block_entry.archived_size))
+      } else if block_entry.flags & MPQ_FILE_COMPRESS != 0
+        && (force_decompress || block_entry.size > block_entry.archived_size)
       {
-        file_data = MPQArchive::decompress(file_data);
+        file_data = MPQArchive::decompress(file_data)?;
       }
 
       return Some(file_data);
@@ -428,14 +356,11 @@ impl MPQArchive {
     hash_table: &[MPQTableEntry],
     block_table: &[MPQTableEntry],
   ) -> Option<BlockTableEntry> {
-    let hash_entry_wrapper =
-      MPQArchive::get_hash_table_entry(encryption_table, hash_table, archive_filename);
-    let hash_entry = match hash_entry_wrapper {
-      Some(entry) => entry,
-      None => return None,
-    };
+    let hash_entry =
+      MPQArchive::get_hash_table_entry(encryption_table, hash_table, archive_filename)?;
 
-    match &block_table[hash_entry.block_table_index as usize] {
+    // corrupted hash entries can point past the block table
+    match block_table.get(hash_entry.block_table_index as usize)? {
       MPQTableEntry::Block(entry) => Some(*entry),
       _other => panic!("Not block entry"),
     }
@@ -449,7 +374,7 @@ impl MPQArchive {
       &self.encryption_table,
       &self.hash_table,
       &self.block_table,
-    ).expect("Couldn't find block table entry");
+    )?;
     let force_decompress = false;
 
     MPQArchive::_read_file(
@@ -479,11 +404,11 @@ impl MPQArchive {
     None
   }
 
-  fn decompress(data: Vec<u8>) -> Vec<u8> {
+  fn decompress(data: Vec<u8>) -> Option<Vec<u8>> {
     let compression_type = data[0];
 
     if compression_type == 0 {
-      data
+      Some(data)
     } else if compression_type == 2 {
       panic!("zlib compression not implemented yet");
     } else if compression_type == 16 {
@@ -495,9 +420,9 @@ impl MPQArchive {
 
       let mut decompressed_data = vec![];
       let mut reader = DecoderReader::new(&data[1..]);
-      copy(&mut reader, &mut decompressed_data);
+      copy(&mut reader, &mut decompressed_data).ok()?;
 
-      decompressed_data
+      Some(decompressed_data)
     } else {
       panic!("Unsupported compression type")
     }
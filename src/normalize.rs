@@ -0,0 +1,2 @@
+pub mod races;
+pub mod maps;
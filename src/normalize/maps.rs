@@ -0,0 +1,121 @@
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+// names, renames and localized titles of ladder and tournament maps
+const EMBEDDED_MAPS: &str = include_str!("maps.csv");
+
+// suffix of ladder map titles
+const LADDER_SUFFIX: &str = " LE";
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MapName {
+  // stable across renames and aliases, e.g. "post-youth"
+  pub id: String,
+  pub name: String,
+}
+
+pub struct MapNames {
+  // lowercase alias -> canonical map
+  aliases: HashMap<String, MapName>,
+}
+
+impl MapNames {
+  // only the embedded map table
+  pub fn new() -> MapNames {
+    let mut maps = MapNames {
+      aliases: HashMap::new(),
+    };
+    maps.extend_csv(EMBEDDED_MAPS).expect("Couldn't parse embedded maps");
+    maps
+  }
+
+  // adds "id,name" lines, the first name of a new id is its canonical name.
+  // blank lines and lines starting with # are ignored
+  pub fn extend_csv(&mut self, contents: &str) -> Result<(), &'static str> {
+    for line in contents.lines() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+
+      let (id, name) = match line.split_once(',') {
+        Some((id, name)) if !id.trim().is_empty() && !name.trim().is_empty() => (id.trim(), name.trim()),
+        _other => return Err("Map line is missing an id or name"),
+      };
+
+      let canonical = match self.aliases.values().find(|map| map.id == id) {
+        Some(map) => map.clone(),
+        None => MapName {
+          id: id.to_string(),
+          name: name.to_string(),
+        },
+      };
+      self.aliases.insert(strip_title(name).to_lowercase(), canonical);
+    }
+
+    Ok(())
+  }
+
+  // maps that aren't in the table keep their stripped title with an id derived from it
+  pub fn normalize(&self, title: &str) -> MapName {
+    let name = strip_title(title);
+    match self.aliases.get(&name.to_lowercase()) {
+      Some(map) => map.clone(),
+      None => MapName {
+        id: map_id(name),
+        name: name.to_string(),
+      },
+    }
+  }
+}
+
+// removes tournament tags like "[TLMC14] " or "[ESL] " and the ladder suffix
+fn strip_title(title: &str) -> &str {
+  let mut title = title.trim();
+  while title.starts_with('[') {
+    match title.find(']') {
+      Some(tag_end) => title = title[tag_end + 1..].trim_start(),
+      None => break,
+    }
+  }
+
+  title.trim_end_matches(LADDER_SUFFIX).trim_end()
+}
+
+// lowercase words joined by dashes, e.g. "King's Cove" -> "kings-cove"
+fn map_id(name: &str) -> String {
+  name
+    .to_lowercase()
+    .replace('\'', "")
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|word| !word.is_empty())
+    .collect::<Vec<&str>>()
+    .join("-")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn normalizes_localized_titles() {
+    let maps = MapNames::new();
+    let eternal_empire = MapName {
+      id: "eternal-empire".to_string(),
+      name: "Eternal Empire".to_string(),
+    };
+    assert_eq!(maps.normalize("Вечная империя LE"), eternal_empire);
+    assert_eq!(maps.normalize("永恒帝国 LE"), eternal_empire);
+    assert_eq!(maps.normalize("[ESL] Empire Éternel LE"), eternal_empire);
+  }
+
+  #[test]
+  fn keeps_unknown_titles() {
+    let maps = MapNames::new();
+    assert_eq!(maps.normalize("[TLMC14] King's Landing LE"), MapName {
+      id: "kings-landing".to_string(),
+      name: "King's Landing".to_string(),
+    });
+  }
+}
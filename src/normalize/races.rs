@@ -0,0 +1,58 @@
+// m_race is the race name in the client's language. es, fr and it
+// clients use the English names
+const RACE_NAMES: [(&str, &str); 35] = [
+  // SelectedRace and AssignedRace in metadata
+  ("Terr", "Terran"),
+  ("Prot", "Protoss"),
+  ("Rand", "Random"),
+  // de
+  ("Terraner", "Terran"),
+  ("Zufällig", "Random"),
+  // es
+  ("Aleatorio", "Random"),
+  ("Aleatoria", "Random"),
+  // fr
+  ("Aléatoire", "Random"),
+  // it
+  ("Casuale", "Random"),
+  // pl
+  ("Terranie", "Terran"),
+  ("Zergi", "Zerg"),
+  ("Protosi", "Protoss"),
+  ("Losowa", "Random"),
+  // pt
+  ("Terranos", "Terran"),
+  ("Terrano", "Terran"),
+  ("Aleatório", "Random"),
+  ("Aleatória", "Random"),
+  // ru
+  ("Терраны", "Terran"),
+  ("Зерги", "Zerg"),
+  ("Протоссы", "Protoss"),
+  ("Случайная", "Random"),
+  ("Случайно", "Random"),
+  // ko
+  ("테란", "Terran"),
+  ("저그", "Zerg"),
+  ("프로토스", "Protoss"),
+  ("무작위", "Random"),
+  // zh-CN
+  ("人类", "Terran"),
+  ("异虫", "Zerg"),
+  ("星灵", "Protoss"),
+  ("随机", "Random"),
+  // zh-TW
+  ("人類", "Terran"),
+  ("蟲族", "Zerg"),
+  ("神族", "Protoss"),
+  ("隨機", "Random"),
+  ("隨機選擇", "Random"),
+];
+
+// English race name, unknown names are kept as they are
+pub fn normalize_race(race: &str) -> &str {
+  match RACE_NAMES.iter().find(|(name, _)| *name == race) {
+    Some((_, race)) => race,
+    None => race,
+  }
+}
@@ -1,36 +1,97 @@
 use serde::Serialize;
 
 use crate::{Player, TinybirdGame, TinybirdTimelineEntry};
-use crate::game::Game;
+use crate::game::{BuildEntry, Engagement, Expansion, Game, ObjectLocation, MAX_SUPPLY, Score, WorkerDeath};
 use crate::replay::{Metadata, Replay, Event};
 use crate::decoders::DecoderResult;
 use crate::events::player_stats_event::PlayerStatsEvent;
 use crate::events::object_event::ObjectEvent;
-
-use std::collections::HashMap;
-
-pub type RaceMappings<'a> = HashMap<&'a str, &'a str>;
-
-pub struct ReplayParser<'a> {
+use crate::events::upgrade_event::UpgradeEvent;
+use crate::events::positions_event::PositionsEvent;
+use crate::events::owner_change_event::OwnerChangeEvent;
+use crate::time::{Gameloop, PlayedAt};
+use crate::result::{decide_result, GameResult, ResultSource};
+use crate::protocol::GAME_USER_LEAVE_EVENT;
+use crate::analysis::mechanics::{player_mechanics, Mechanics};
+use crate::analysis::commands::{player_commands, Command};
+use crate::analysis::abilities::Abilities;
+use crate::analysis::macro_metrics::{player_macro_metrics, MacroMetrics};
+use crate::analysis::control_groups::{player_control_groups, ControlGroups};
+use crate::analysis::camera::{player_camera_usage, CameraUsage};
+use crate::analysis::ending::{game_ending, Ending};
+use crate::normalize::races::normalize_race;
+use crate::normalize::maps::MapNames;
+
+// prepended to proxied buildings in build strings, e.g. "ProxyBarracks"
+const PROXY_PREFIX: &str = "Proxy";
+
+pub struct ReplayParser {
   names: Vec<String>,
   context: TimelineContext,
-  race_mappings: RaceMappings<'a>,
   events: Vec<Event>,
+  pub abilities: Abilities,
+  pub maps: MapNames,
   pub game: Game,
   pub timeline: Vec<TinybirdTimelineEntry>,
 }
 
+// per player fields are indexed by player id - 1
 #[derive(Clone, Serialize)]
 pub struct ReplaySummary {
   pub players: Vec<Player>,
-  pub builds: [Vec<String>; 2],
-  pub build_mappings: [u16; 2],
-  // pub units: [Vec<String>; 2],
-  // pub unit_mappings: [u16; 2],
-  pub winner: u8,
+  pub builds: Vec<Vec<String>>,
+  pub build_mappings: Vec<u16>,
+  pub build_times: Vec<Vec<BuildEntry>>,
+  pub upgrades: Vec<Vec<(String, Gameloop)>>,
+  // last score snapshot of each player
+  pub scores: Vec<Score>,
+  pub building_locations: Vec<Vec<ObjectLocation>>,
+  pub death_locations: Vec<Vec<ObjectLocation>>,
+  pub base_locations: Vec<Vec<ObjectLocation>>,
+  // (gameloop, used, made) at every unit birth and death
+  pub supply: Vec<Vec<(Gameloop, f32, f32)>>,
+  // (start, end) gameloops
+  pub supply_blocks: Vec<Vec<(Gameloop, Gameloop)>>,
+  // seconds spent supply blocked
+  pub supply_blocked: Vec<u16>,
+  // apm, epm and spm averages with a per minute timeline
+  pub mechanics: Vec<Mechanics>,
+  // empty unless SCmdEvent is decoded
+  pub commands: Vec<Vec<Command>>,
+  pub macro_metrics: Vec<MacroMetrics>,
+  // empty unless SControlGroupUpdateEvent and SSelectionDeltaEvent are decoded
+  pub control_groups: Vec<ControlGroups>,
+  // empty unless SCameraUpdateEvent is decoded
+  pub camera: Vec<CameraUsage>,
+  // how the game ended and who left first
+  pub ending: Ending,
+  pub worker_births: Vec<Vec<Gameloop>>,
+  pub worker_deaths: Vec<Vec<WorkerDeath>>,
+  pub workers_lost: Vec<u16>,
+  pub workers_killed: Vec<u16>,
+  pub engagements: Vec<Engagement>,
+  pub expansions: Vec<Vec<Expansion>>,
+  // (gameloop, bases) whenever a base finishes or is destroyed
+  pub base_counts: Vec<Vec<(Gameloop, u8)>>,
+  // gameloops the 2nd, 3rd and 4th bases were started
+  pub second_base: Vec<Option<Gameloop>>,
+  pub third_base: Vec<Option<Gameloop>>,
+  pub fourth_base: Vec<Option<Gameloop>>,
+  pub units: Vec<Vec<String>>,
+  pub unit_mappings: Vec<u16>,
+  pub result: GameResult,
+  pub result_source: ResultSource,
+  // None for ties and unknown results
+  pub winner: Option<u8>,
+  pub winning_team: Option<u8>,
+  // races sorted within each team, e.g. "Protoss,Zerg" or "Protoss,ZergvTerran,Terran"
+  pub matchup: String,
   pub game_length: u16,
   pub map: String,
+  pub map_id: String,
   pub played_at: u64,
+  // seconds east of UTC where the replay was recorded
+  pub timezone_offset: i64,
   pub tags: String,
   pub tinybird: TinybirdGame,
   pub timeline: Vec<TinybirdTimelineEntry>,
@@ -40,9 +101,9 @@ pub struct ReplaySummary {
 pub struct TimelineContext {
   pub content_hash: String,
   pub players: Vec<Player>,
-  pub workers_lost: [u16; 2],
-  pub workers_killed: [u16; 2],
-  pub winner_id: u8,
+  pub workers_lost: Vec<u16>,
+  pub workers_killed: Vec<u16>,
+  pub winning_team: Option<u8>,
   pub map: String,
   pub event: String,
   pub matchup: String,
@@ -51,41 +112,37 @@ pub struct TimelineContext {
   pub game_version: String,
 }
 
-impl<'a> ReplayParser<'a> {
-  pub fn new() -> ReplayParser<'a> {
-    let race_mappings: RaceMappings = HashMap::from([
-      ("저그", "Zerg"),
-      ("异虫", "Zerg"),
-      ("蟲族", "Zerg"),
-      ("Zergi", "Zerg"),
-      ("테란", "Terran"),
-      ("人類", "Terran"),
-      ("人类", "Terran"),
-      ("Terraner", "Terran"),
-      ("Терраны", "Terran"),
-      ("프로토스", "Protoss"),
-      ("神族", "Protoss"),
-      ("Protosi", "Protoss"),
-      ("星灵", "Protoss"),
-      ("Протоссы", "Protoss"),
-    ]);
+impl ReplayParser {
+  pub fn new() -> ReplayParser {
     let game = Game::new();
     let timeline: Vec<TinybirdTimelineEntry> = vec![];
 
     ReplayParser {
       names: vec![],
-      race_mappings,
       context: Default::default(),
       events: vec![],
+      abilities: Abilities::new(),
+      maps: MapNames::new(),
       game,
       timeline,
     }
   }
 
   pub fn reset(&mut self, new_context: TimelineContext, new_events: Vec<Event>) {
+    // ids of observers don't have a team
+    let player_count = new_context.players
+      .iter()
+      .map(|player| player.id as usize)
+      .max()
+      .unwrap_or(0);
+    let mut teams = vec![None; player_count];
+    for player in &new_context.players {
+      teams[(player.id - 1) as usize] = Some(player.team);
+    }
+
     self.context = new_context;
     self.events = new_events;
-    self.game.reset();
+    self.game.reset(teams);
     self.timeline.clear();
   }
 
@@ -94,24 +151,44 @@ impl<'a> ReplayParser<'a> {
       if let DecoderResult::Name(name) = &event.entries.last().unwrap().1 {
         match name.as_str() {
           "NNet.Replay.Tracker.SPlayerStatsEvent" => {
-            PlayerStatsEvent::new(
+            PlayerStatsEvent::handle(
               &self.context,
               &mut self.game,
               &mut self.timeline,
               event,
-            );
+            )?;
           },
           "NNet.Replay.Tracker.SUnitInitEvent" |
           "NNet.Replay.Tracker.SUnitBornEvent" |
           "NNet.Replay.Tracker.SUnitTypeChangeEvent" |
-          "NNet.Replay.Tracker.SUnitDiedEvent" => {
-            ObjectEvent::new(
+          "NNet.Replay.Tracker.SUnitDiedEvent" |
+          "NNet.Replay.Tracker.SUnitDoneEvent" => {
+            ObjectEvent::handle(
               &mut self.names,
               &mut self.context,
               &mut self.game,
               event,
               name,
-            );
+            )?;
+          },
+          "NNet.Replay.Tracker.SUpgradeEvent" => {
+            UpgradeEvent::handle(
+              &self.context,
+              &mut self.game,
+              event,
+            )?;
+          },
+          "NNet.Replay.Tracker.SUnitPositionsEvent" => {
+            PositionsEvent::handle(
+              &mut self.game,
+              event,
+            )?;
+          },
+          "NNet.Replay.Tracker.SUnitOwnerChangeEvent" => {
+            OwnerChangeEvent::handle(
+              &mut self.game,
+              event,
+            )?;
           },
           _other => (),
         }
@@ -136,51 +213,40 @@ impl<'a> ReplayParser<'a> {
     &mut self,
     raw_replay: Replay,
     builds: &mut Vec<String>,
-    // units: &mut Vec<String>,
+    units: &mut Vec<String>,
   ) -> Result<ReplaySummary, &'static str> {
     let replay = raw_replay.parsed;
     let tags = replay.tags.clone();
 
-    let parsed_metadata: Metadata = serde_json::from_str(&replay.metadata).unwrap();
+    let parsed_metadata: Option<Metadata> = match &replay.metadata {
+      Some(metadata) => match serde_json::from_str(metadata) {
+        Ok(parsed) => Some(parsed),
+        Err(_) => return Err("couldn't parse metadata"),
+      },
+      None => None,
+    };
 
-    let winner = match parsed_metadata.Players
-      .iter()
-      .find(|player| player.Result == "Win") {
-        Some(player) => player.PlayerID,
-        None => return Err("couldn't find winner"),
-      };
-    let game_length = parsed_metadata.Duration;
+    // metadata duration is rounded and missing from older replays
+    let game_length = match (Replay::elapsed_gameloops(&replay.header), &parsed_metadata) {
+      (Gameloop(0), Some(metadata)) => metadata.duration,
+      (gameloops, _) => gameloops.whole_seconds(),
+    };
+    let game_end = match Replay::elapsed_gameloops(&replay.header) {
+      Gameloop(0) => Gameloop::from_seconds(game_length as u32),
+      gameloops => gameloops,
+    };
 
     let raw_map = &replay.player_info
       .iter()
       .find(|(field, _)| *field == "m_title")
       .unwrap().1;
-    let mut map = "";
-    if let DecoderResult::Blob(value) = raw_map {
-      map = value
-        .trim_start_matches("[M] ")
-        .trim_start_matches("[SO] ")
-        .trim_start_matches("[ESL] ")
-        .trim_start_matches("[GSL] ")
-        .trim_start_matches("[TLMC14] ")
-        .trim_start_matches("[TLMC15] ")
-        .trim_start_matches("[TLMC16] ")
-        .trim_start_matches("[TLMC17] ")
-        .trim_end_matches(" LE");
-    }
+    let map = match raw_map {
+      DecoderResult::Blob(value) => self.maps.normalize(value),
+      _other => self.maps.normalize(""),
+    };
 
-    let raw_played_at = &replay.player_info
-      .iter()
-      .find(|(field, _)| *field == "m_timeUTC")
-      .unwrap().1;
-    let mut played_at = 0;
-    if let DecoderResult::Value(value) = raw_played_at {
-      // TODO: this truncation is not working properly
-      played_at = value.clone() as u64;
-    }
-    // game records time in window epoch for some reason
-    // https://en.wikipedia.org/wiki/Epoch_(computing)
-    played_at = (played_at / 10000000) - 11644473600;
+    let replay_time = PlayedAt::from_details(&replay.player_info).unwrap_or_default();
+    let played_at = replay_time.utc;
 
     let (_, player_list) = &replay.player_info
       .iter()
@@ -192,25 +258,26 @@ impl<'a> ReplayParser<'a> {
       DecoderResult::Array(values) => {
         // TODO: enumerated id is incorrect for P1 and P2 in games
 
-        // don't support 1 player or 3+ player games
-        if values.len() != 2 {
-          return Err("Not 2 players in replay");
-        }
-
         for (id, player) in values.iter().enumerate() {
           match player {
             DecoderResult::Struct(player_values) => {
+              // observers and referees can be in the player list, they keep their id
+              if let Some((_, DecoderResult::Value(observe))) = player_values
+                .iter()
+                .find(|(field, _)| *field == "m_observe")
+              {
+                if *observe != 0 {
+                  continue;
+                }
+              }
+
               let raw_race = &player_values
                 .iter()
                 .find(|(field, _)| *field == "m_race")
                 .unwrap().1;
               let mut race = String::new();
               if let DecoderResult::Blob(value) = raw_race {
-                race = value.clone();
-              }
-
-              if let Some(value) = self.race_mappings.get(race.as_str()) {
-                race = value.to_string();
+                race = normalize_race(value).to_string();
               }
 
               let raw_name = &player_values
@@ -225,10 +292,31 @@ impl<'a> ReplayParser<'a> {
                 };
               }
 
+              // every player is on their own team if there are no teams
+              let team = match player_values.iter().find(|(field, _)| *field == "m_teamId") {
+                Some((_, DecoderResult::Value(team))) => *team as u8,
+                _other => id as u8,
+              };
+
+              // details only have the race played, metadata and lobby slots have the race picked
+              let player_id = (id + 1) as u8;
+              let selected_race = match parsed_metadata
+                .as_ref()
+                .and_then(|metadata| metadata.selected_race(player_id))
+              {
+                Some(selected_race) => Some(selected_race.to_string()),
+                None => replay.random_players.get(&player_id).map(|random| match random {
+                  true => "Random".to_string(),
+                  false => race.clone(),
+                }),
+              };
+
               players.push(Player {
-                id: (id + 1) as u8,
+                id: player_id,
                 race,
+                selected_race,
                 name,
+                team,
               });
             },
             _other => panic!("Found DecoderResult::{:?}", _other)
@@ -238,52 +326,95 @@ impl<'a> ReplayParser<'a> {
       _other => panic!("Found DecoderResult::{:?}", _other)
     }
 
+    players.sort_by_key(|player| player.id);
+
+    let leave_events: Vec<&Event> = replay.game_events
+      .iter()
+      .filter(|event| event.entries.iter().any(|(field, value)| match value {
+        DecoderResult::Name(name) => field == "_event" && name == GAME_USER_LEAVE_EVENT,
+        _other => false,
+      }))
+      .collect();
+    let (result, result_source) = decide_result(
+      parsed_metadata.as_ref(),
+      &replay.player_info,
+      &leave_events,
+      &replay.user_players,
+      &players,
+    );
+    let winning_team = result.winning_team();
+
+    // without a winner the first player stands in for it in 1v1 oriented outputs,
+    // these games aren't written to Tinybird
+    let winner = match winning_team {
+      Some(team) => players.iter().find(|player| player.team == team),
+      None => players.first(),
+    };
+    let winner = match winner {
+      Some(player) => player.id,
+      None => return Err("No players in replay"),
+    };
+    let winner_team = players.iter().find(|player| player.id == winner).unwrap().team;
+
+    // the first player on another team is the loser in 1v1 oriented outputs
+    let loser = match players.iter().find(|player| player.team != winner_team) {
+      Some(player) => player.id,
+      None => return Err("No opponents in replay"),
+    };
+
     let mut serialized_players = vec![];
-    let mut serialized_matchup = vec![];
     for player in &players {
       serialized_players.push(player.name.clone());
-      serialized_matchup.push(player.race.clone());
     }
     serialized_players.sort();
-    serialized_matchup.sort();
-
-    let loser: u8 = if winner == 1 {
-      2
-    } else {
-      1
-    };
-
-    players.sort_by(|a, b| a.id.cmp(&b.id));
 
     let context = TimelineContext {
       content_hash: raw_replay.content_hash.clone(),
       players: players.clone(),
-      workers_lost: [0, 0],
-      workers_killed: [0, 0],
-      winner_id: winner,
-      matchup: serialized_matchup.join(""),
-      map: map.to_owned(),
+      workers_lost: vec![],
+      workers_killed: vec![],
+      winning_team,
+      matchup: team_matchup(&players, ""),
+      map: map.name.clone(),
       event: tags.clone(),
       game_length,
       played_at,
-      game_version: parsed_metadata.GameVersion.to_string(),
+      game_version: match &parsed_metadata {
+        Some(metadata) => metadata.game_version.to_string(),
+        None => String::new(),
+      },
     };
-    
+
     // event parser owns events now
     self.reset(context, replay.tracker_events);
-    self.parse_events();
-
-    let mut replay_build_mappings: [u16; 2] = [0, 0];
-    let mut replay_builds: [Vec<String>; 2] = [vec![], vec![]];
+    let player_count = self.game.teams.len();
+    self.context.workers_lost = vec![0; player_count];
+    self.context.workers_killed = vec![0; player_count];
+    self.parse_events()?;
+    self.game.mark_proxies(&self.names);
+
+    let mut replay_build_mappings: Vec<u16> = vec![0; player_count];
+    let mut replay_builds: Vec<Vec<String>> = vec![vec![]; player_count];
+    let mut replay_build_times: Vec<Vec<BuildEntry>> = vec![vec![]; player_count];
     for (replay_build_index, build) in self.game.builds.iter_mut().enumerate() {
-      if build.len() == 0 {
+      // observers don't have builds
+      if self.game.teams[replay_build_index].is_none() {
+        continue;
+      }
+
+      if build.is_empty() {
         return Err("build is length 0");
       }
 
-      build.sort_by(|a, b| a.1.cmp(&b.1));
+      build.sort_by_key(|entry| entry.started);
+      replay_build_times[replay_build_index] = build.clone();
       replay_builds[replay_build_index] = build
         .iter()
-        .map(|(building, _)| building.to_owned())
+        .map(|build_entry| if build_entry.proxy {
+          format!("{PROXY_PREFIX}{}", build_entry.name)
+        } else {
+          build_entry.name.to_owned()
+        })
         .collect::<Vec<String>>();
 
       let joined_build = replay_builds[replay_build_index].join(",");
@@ -296,24 +427,142 @@ impl<'a> ReplayParser<'a> {
       }
     }
 
-    // let mut replay_units_mappings: [u16; 2] = [0, 0];
-    // let mut replay_units: [Vec<String>; 2] = [vec![], vec![]];
-    // for (replay_unit_index, unit) in game.units.iter_mut().enumerate() {
-    //   unit.sort_by(|a, b| a.1.cmp(&b.1));
-    //   replay_units[replay_unit_index] = unit
-    //     .iter()
-    //     .map(|(unit, _)| unit.to_owned())
-    //     .collect::<Vec<String>>();
-
-    //   let joined_units = replay_units[replay_unit_index].join(",");
-    //   match units.iter().position(|seen_units| &joined_units == seen_units) {
-    //     Some(unit_index) => replay_units_mappings[replay_unit_index] = unit_index as u16,
-    //     None => {
-    //       units.push(joined_units);
-    //       replay_units_mappings[replay_unit_index] = units.len() as u16 - 1;
-    //     }
-    //   }
-    // }
+    let mut replay_upgrades: Vec<Vec<(String, Gameloop)>> = vec![vec![]; player_count];
+    for (replay_upgrade_index, upgrades) in self.game.upgrades.iter_mut().enumerate() {
+      upgrades.sort_by_key(|upgrade| upgrade.1);
+      replay_upgrades[replay_upgrade_index] = upgrades.clone();
+    }
+
+    let mut scores: Vec<Score> = vec![Score::default(); player_count];
+    for (player_index, player_scores) in self.game.scores.iter().enumerate() {
+      if let Some(score) = player_scores.last() {
+        scores[player_index] = score.clone();
+      }
+    }
+
+    let mut building_locations: Vec<Vec<ObjectLocation>> = vec![vec![]; player_count];
+    let mut death_locations: Vec<Vec<ObjectLocation>> = vec![vec![]; player_count];
+    let mut base_locations: Vec<Vec<ObjectLocation>> = vec![vec![]; player_count];
+    for player in &players {
+      let player_index = (player.id - 1) as usize;
+      building_locations[player_index] = self.game.building_locations(&self.names, player.id);
+      death_locations[player_index] = self.game.death_locations(&self.names, player.id);
+      base_locations[player_index] = self.game.base_locations(&self.names, player.id);
+    }
+
+    let mut supply: Vec<Vec<(Gameloop, f32, f32)>> = vec![vec![]; player_count];
+    let mut supply_blocks: Vec<Vec<(Gameloop, Gameloop)>> = vec![vec![]; player_count];
+    let mut supply_blocked: Vec<u16> = vec![0; player_count];
+    for (player_index, tracker) in self.game.supply.iter().enumerate() {
+      supply[player_index] = tracker.changes
+        .iter()
+        .map(|(gameloop, used, made)| (*gameloop, *used, made.min(MAX_SUPPLY)))
+        .collect();
+      supply_blocks[player_index] = tracker.blocks();
+
+      let blocked_gameloops: u32 = supply_blocks[player_index]
+        .iter()
+        .map(|(start, end)| (*end - *start).0)
+        .sum();
+      supply_blocked[player_index] = Gameloop(blocked_gameloops).seconds().round() as u16;
+    }
+
+    let mut mechanics = player_mechanics(
+      &replay.game_events,
+      &replay.user_players,
+      player_count,
+      game_end,
+    );
+    // metadata only has an overall apm, used when game events weren't decoded
+    if mechanics.is_empty() {
+      mechanics = vec![Mechanics::default(); player_count];
+      if let Some(metadata) = &parsed_metadata {
+        for player in &metadata.players {
+          let player_index = (player.player_id as usize).wrapping_sub(1);
+          if let Some(player_mechanics) = mechanics.get_mut(player_index) {
+            player_mechanics.apm = player.apm.round() as u16;
+          }
+        }
+      }
+    }
+
+    let commands = player_commands(
+      &replay.game_events,
+      &replay.user_players,
+      player_count,
+      &self.abilities,
+    );
+    let macro_metrics = player_macro_metrics(
+      &self.game,
+      &self.names,
+      &commands,
+      &self.abilities,
+      game_end,
+    );
+    let control_groups = player_control_groups(
+      &replay.game_events,
+      &replay.user_players,
+      player_count,
+      &self.game,
+      &self.names,
+    );
+    let camera = player_camera_usage(
+      &replay.game_events,
+      &replay.user_players,
+      player_count,
+      replay.map_size,
+      &self.game,
+      &self.names,
+      game_end,
+    );
+    let ending = game_ending(
+      &replay.game_events,
+      &replay.message_events,
+      &replay.user_players,
+      &self.game,
+      &self.names,
+    );
+
+    let mut expansions: Vec<Vec<Expansion>> = vec![vec![]; player_count];
+    let mut base_counts: Vec<Vec<(Gameloop, u8)>> = vec![vec![]; player_count];
+    for player in &players {
+      let player_index = (player.id - 1) as usize;
+      expansions[player_index] = self.game.expansions(&self.names, player.id);
+      base_counts[player_index] = self.game.base_counts(&self.names, player.id);
+    }
+
+    let base_started = |base_index: usize| -> Vec<Option<Gameloop>> {
+      expansions
+        .iter()
+        .map(|player_expansions| player_expansions.get(base_index).map(|expansion| expansion.started))
+        .collect()
+    };
+    let second_base = base_started(1);
+    let third_base = base_started(2);
+    let fourth_base = base_started(3);
+
+    let mut replay_units_mappings: Vec<u16> = vec![0; player_count];
+    let mut replay_units: Vec<Vec<String>> = vec![vec![]; player_count];
+    for (replay_unit_index, unit) in self.game.units.iter_mut().enumerate() {
+      if self.game.teams[replay_unit_index].is_none() {
+        continue;
+      }
+
+      unit.sort_by_key(|entry| entry.started);
+      replay_units[replay_unit_index] = unit
+        .iter()
+        .map(|unit_entry| unit_entry.name.to_owned())
+        .collect::<Vec<String>>();
+
+      let joined_units = replay_units[replay_unit_index].join(",");
+      match units.iter().position(|seen_units| &joined_units == seen_units) {
+        Some(unit_index) => replay_units_mappings[replay_unit_index] = unit_index as u16,
+        None => {
+          units.push(joined_units);
+          replay_units_mappings[replay_unit_index] = units.len() as u16 - 1;
+        }
+      }
+    }
 
     const GAS_BUILDINGS: [&str; 3] = [
       "Assimilator",
@@ -321,50 +570,130 @@ impl<'a> ReplayParser<'a> {
       "Extractor",
     ];
 
-    let winner_build = replay_builds[(winner - 1) as usize]
+    let winner_index = (winner - 1) as usize;
+    let loser_index = (loser - 1) as usize;
+    let winner_player = players.iter().find(|player| player.id == winner).unwrap();
+    let loser_player = players.iter().find(|player| player.id == loser).unwrap();
+
+    let winner_build = replay_builds[winner_index]
       .iter()
-      .filter(|building| !GAS_BUILDINGS.contains(&building.as_str()))
+      .filter(|building| !GAS_BUILDINGS.contains(&building.trim_start_matches(PROXY_PREFIX)))
       .map(|building| building.to_string())
       .collect::<Vec<String>>()
       .join(",");
-    let loser_build = replay_builds[(loser - 1) as usize]
+    let loser_build = replay_builds[loser_index]
       .iter()
-      .filter(|building| !GAS_BUILDINGS.contains(&building.as_str()))
+      .filter(|building| !GAS_BUILDINGS.contains(&building.trim_start_matches(PROXY_PREFIX)))
       .map(|building| building.to_string())
       .collect::<Vec<String>>()
       .join(",");
 
+    let winner_upgrades = replay_upgrades[winner_index]
+      .iter()
+      .map(|(upgrade, _)| upgrade.to_string())
+      .collect::<Vec<String>>()
+      .join(",");
+    let loser_upgrades = replay_upgrades[loser_index]
+      .iter()
+      .map(|(upgrade, _)| upgrade.to_string())
+      .collect::<Vec<String>>()
+      .join(",");
+
     let tinybird_game = TinybirdGame {
       content_hash: raw_replay.content_hash.clone(),
+      result: result.as_str().to_string(),
+      result_source: result_source.as_str().to_string(),
       winner_id: winner,
-      winner_name: players[(winner - 1) as usize].name.clone(),
-      winner_race: players[(winner - 1) as usize].race.clone(),
+      winner_name: winner_player.name.clone(),
+      winner_race: winner_player.race.clone(),
+      winner_selected_race: winner_player.selected_race.clone(),
       winner_build: winner_build.clone(),
+      winner_upgrades,
       loser_id: loser,
-      loser_name: players[(loser - 1) as usize].name.clone(),
-      loser_race: players[(loser - 1) as usize].race.clone(),
+      loser_name: loser_player.name.clone(),
+      loser_race: loser_player.race.clone(),
+      loser_selected_race: loser_player.selected_race.clone(),
       loser_build: loser_build.clone(),
-      matchup: serialized_matchup.join(""),
+      loser_upgrades,
+      winner_second_base: second_base[winner_index],
+      winner_third_base: third_base[winner_index],
+      winner_fourth_base: fourth_base[winner_index],
+      loser_second_base: second_base[loser_index],
+      loser_third_base: third_base[loser_index],
+      loser_fourth_base: fourth_base[loser_index],
+      winner_apm: mechanics[winner_index].apm,
+      winner_epm: mechanics[winner_index].epm,
+      winner_spm: mechanics[winner_index].spm,
+      loser_apm: mechanics[loser_index].apm,
+      loser_epm: mechanics[loser_index].epm,
+      loser_spm: mechanics[loser_index].spm,
+      winner_inject_uptime: macro_metrics[winner_index].average_inject_uptime,
+      winner_chrono_boosts: macro_metrics[winner_index].chrono_boosts,
+      winner_mules: macro_metrics[winner_index].mules,
+      winner_energy_efficiency: macro_metrics[winner_index].energy_efficiency,
+      winner_idle_production: macro_metrics[winner_index].idle_production,
+      loser_inject_uptime: macro_metrics[loser_index].average_inject_uptime,
+      loser_chrono_boosts: macro_metrics[loser_index].chrono_boosts,
+      loser_mules: macro_metrics[loser_index].mules,
+      loser_energy_efficiency: macro_metrics[loser_index].energy_efficiency,
+      loser_idle_production: macro_metrics[loser_index].idle_production,
+      ending: ending.ending.as_str().to_string(),
+      first_leaver_id: ending.first_leaver,
+      first_leave_gameloop: ending.first_leave_gameloop,
+      matchup: team_matchup(&players, ""),
       player_names: serialized_players.join(""),
       players: serde_json::to_string(&players).unwrap(),
       builds: serde_json::to_string(&replay_builds).unwrap(),
-      map: map.to_owned(),
+      build_times: serde_json::to_string(&replay_build_times).unwrap(),
+      map: map.name.clone(),
+      map_id: map.id.clone(),
       game_length,
       played_at,
       event: replay.tags.clone(),
-      // game_version: parsed_metadata.GameVersion.to_string(),
+      // game_version: parsed_metadata.game_version.to_string(),
     };
 
     let replay_summary: ReplaySummary = ReplaySummary {
+      matchup: team_matchup(&players, ","),
       players,
       builds: replay_builds,
       build_mappings: replay_build_mappings,
-      // units: replay_units,
-      // unit_mappings: replay_units_mappings,
-      winner,
+      build_times: replay_build_times.clone(),
+      upgrades: replay_upgrades,
+      scores,
+      building_locations,
+      death_locations,
+      base_locations,
+      supply,
+      supply_blocks,
+      supply_blocked,
+      mechanics,
+      commands,
+      macro_metrics,
+      control_groups,
+      camera,
+      ending,
+      worker_births: self.game.worker_births.clone(),
+      worker_deaths: self.game.worker_deaths.clone(),
+      workers_lost: self.context.workers_lost.clone(),
+      workers_killed: self.context.workers_killed.clone(),
+      engagements: self.game.engagements(&self.names),
+      expansions,
+      base_counts,
+      second_base,
+      third_base,
+      fourth_base,
+      units: replay_units,
+      unit_mappings: replay_units_mappings,
+      result,
+      result_source,
+      winner: winning_team.map(|_| winner),
+      winning_team,
       game_length,
-      map: map.to_owned(),
+      map: map.name.clone(),
+      map_id: map.id.clone(),
       played_at,
+      timezone_offset: replay_time.local_offset,
       tags: tags.clone(),
       tinybird: tinybird_game,
       timeline: self.timeline.clone(),
@@ -373,3 +702,31 @@ impl<'a> ReplayParser<'a> {
     Ok(replay_summary)
   }
 }
+
+// races sorted within each team, with teams separated by "v" when any team
+// has more than 1 player. 1v1s are just the sorted races, e.g. "Protoss,Zerg"
+fn team_matchup(players: &[Player], separator: &str) -> String {
+  let mut teams: Vec<(u8, Vec<String>)> = vec![];
+  for player in players {
+    match teams.iter_mut().find(|(team, _)| *team == player.team) {
+      Some((_, races)) => races.push(player.race.clone()),
+      None => teams.push((player.team, vec![player.race.clone()])),
+    }
+  }
+
+  if teams.iter().all(|(_, races)| races.len() == 1) {
+    let mut races: Vec<String> = players.iter().map(|player| player.race.clone()).collect();
+    races.sort();
+    return races.join(separator);
+  }
+
+  let mut team_races: Vec<String> = teams
+    .into_iter()
+    .map(|(_, mut races)| {
+      races.sort();
+      races.join(separator)
+    })
+    .collect();
+  team_races.sort();
+  team_races.join("v")
+}
@@ -7,7 +7,8 @@ use crate::decoders::{
 };
 
 use crate::replay::Event;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
 
 // Decoding instructions for each protocol type.
 const RAW_TYPEINFOS: &str = "('_int',[(0,7)]),  #0
@@ -249,11 +250,10 @@ const GAME_DETAILS_TYPEID: u8 = 40;
 //  The typeid of NNet.Replay.SInitData (the type used to store the inital lobby).
 const REPLAY_INITDATA_TYPEID: u8 = 73;
 
-fn instantiate_event_types<'a>() -> (
-  HashMap<i64, (u8, &'a str)>,
-  HashMap<i64, (u8, &'a str)>,
-  HashMap<i64, (u8, &'a str)>,
-) {
+// eventid -> (typeid, name)
+type EventTypes<'a> = HashMap<i64, (u8, &'a str)>;
+
+fn instantiate_event_types<'a>() -> (EventTypes<'a>, EventTypes<'a>, EventTypes<'a>) {
   //  Map from protocol NNet.Game.*Event eventid to (typeid, name)
   let game_event_types: HashMap<i64, (u8, &str)> = HashMap::from([
     (5, (82, "NNet.s.SUserFinishedLoadingSyncEvent")),
@@ -462,7 +462,7 @@ fn handle_int(input: &str) -> Int {
   )
 }
 
-fn handle_array(input: &str) -> ProtocolTypeInfo {
+fn handle_array(input: &str) -> ProtocolTypeInfo<'_> {
   // structure: [(<int>, <int>), <int>], remove only square brackets first to preserve for int
   let parts = input
     .trim_matches(|c: char| c == '[' || c == ']')
@@ -471,7 +471,7 @@ fn handle_array(input: &str) -> ProtocolTypeInfo {
   ProtocolTypeInfo::Array(handle_int(parts.0), parts.1.parse::<u8>().unwrap())
 }
 
-fn handle_optional(input: &str) -> ProtocolTypeInfo {
+fn handle_optional(input: &str) -> ProtocolTypeInfo<'_> {
   let optional = input.trim_matches(match_typeinfo_structure);
   ProtocolTypeInfo::Optional(optional.parse::<u8>().unwrap())
 }
@@ -487,7 +487,7 @@ fn parse_choice(input: &str) -> (&str, u8) {
   )
 }
 
-fn handle_choice(input: &str) -> ProtocolTypeInfo {
+fn handle_choice(input: &str) -> ProtocolTypeInfo<'_> {
   let raw_choice = input
     .trim_matches(|c: char| c == '[' || c == ']' || c == '(')
     .split_once("),")
@@ -510,7 +510,7 @@ fn handle_choice(input: &str) -> ProtocolTypeInfo {
   ProtocolTypeInfo::Choice(int, choices)
 }
 
-fn handle_struct(input: &str) -> ProtocolTypeInfo {
+fn handle_struct(input: &str) -> ProtocolTypeInfo<'_> {
   // only remove square brackets to preserve struct tuples
   let struct_input = input
     .trim_matches(|c: char| c == '[' || c == ']')
@@ -564,19 +564,101 @@ fn parse_typeinfos<'a>() -> Vec<ProtocolTypeInfo<'a>> {
   typeinfos
 }
 
-const ALLOWED_EVENTS: [&str; 5] = [
+const GAME_EVENT_PREFIX: &str = "NNet.Game.";
+
+pub const GAME_USER_LEAVE_EVENT: &str = "NNet.Game.SGameUserLeaveEvent";
+pub const GAME_USER_JOIN_EVENT: &str = "NNet.Game.SGameUserJoinEvent";
+pub const RECONNECT_NOTIFY_MESSAGE: &str = "NNet.Game.SReconnectNotifyMessage";
+
+// events decoded when no other events are requested
+const DEFAULT_TRACKER_EVENTS: [&str; 9] = [
   "NNet.Replay.Tracker.SPlayerStatsEvent",
   "NNet.Replay.Tracker.SUnitInitEvent",
   "NNet.Replay.Tracker.SUnitBornEvent",
   "NNet.Replay.Tracker.SUnitTypeChangeEvent",
   "NNet.Replay.Tracker.SUnitDiedEvent",
+  "NNet.Replay.Tracker.SUpgradeEvent",
+  "NNet.Replay.Tracker.SUnitDoneEvent",
+  "NNet.Replay.Tracker.SUnitPositionsEvent",
+  "NNet.Replay.Tracker.SUnitOwnerChangeEvent",
 ];
 
+// full names of every event in the protocol, built on first use
+// so filters don't rebuild the event tables for every replay
+fn event_names() -> &'static [&'static str] {
+  static EVENT_NAMES: OnceLock<Vec<&'static str>> = OnceLock::new();
+  EVENT_NAMES.get_or_init(|| {
+    let (game_event_types, tracker_event_types, message_event_types) = instantiate_event_types();
+    game_event_types
+      .values()
+      .chain(tracker_event_types.values())
+      .chain(message_event_types.values())
+      .map(|(_, name)| *name)
+      .collect()
+  })
+}
+
+// decides which events are kept, every other event
+// is still decoded to advance the buffer but is then discarded
+#[derive(Clone)]
+pub struct EventFilter {
+  events: HashSet<String>,
+}
+
+impl Default for EventFilter {
+  fn default() -> EventFilter {
+    EventFilter::from(&DEFAULT_TRACKER_EVENTS)
+  }
+}
+
+impl EventFilter {
+  pub fn new() -> EventFilter {
+    EventFilter::default()
+  }
+
+  pub fn from(events: &[&str]) -> EventFilter {
+    let mut filter = EventFilter {
+      events: HashSet::new(),
+    };
+    for event in events {
+      filter.insert(event);
+    }
+
+    filter
+  }
+
+  // accepts full event names or short names like "SUpgradeEvent" or "SCmdEvent".
+  // short names are resolved against the protocol's event tables so only the
+  // stream the event is in is decoded. returns false for unknown events
+  pub fn insert(&mut self, event: &str) -> bool {
+    let event_name = event_names()
+      .iter()
+      .find(|name| **name == event || name.rsplit('.').next() == Some(event));
+
+    match event_name {
+      Some(event_name) => {
+        self.events.insert(event_name.to_string());
+        true
+      },
+      None => false,
+    }
+  }
+
+  // game events are slow to decode and are only read when requested by name
+  pub fn allows_game_events(&self) -> bool {
+    self.events.iter().any(|event| event.starts_with(GAME_EVENT_PREFIX))
+  }
+
+  pub fn allows(&self, event_name: &str) -> bool {
+    self.events.contains(event_name)
+  }
+}
+
 pub struct Protocol<'a> {
   typeinfos: Vec<ProtocolTypeInfo<'a>>,
-  game_event_types: HashMap<i64, (u8, &'a str)>,
-  tracker_event_types: HashMap<i64, (u8, &'a str)>,
-  message_event_types: HashMap<i64, (u8, &'a str)>,
+  game_event_types: EventTypes<'a>,
+  tracker_event_types: EventTypes<'a>,
+  message_event_types: EventTypes<'a>,
 }
 
 impl<'a> Protocol<'a> {
@@ -593,6 +675,16 @@ impl<'a> Protocol<'a> {
     }
   }
 
+  pub fn decode_replay_header(&self, contents: Vec<u8>) -> Vec<EventEntry> {
+    let mut decoder = VersionedDecoder::new(contents, &self.typeinfos);
+    let header = decoder.instance(&self.typeinfos, &REPLAY_HEADER_TYPEID, true);
+
+    match header {
+      DecoderResult::Struct(values) => values,
+      _other => panic!("Found DecoderResult::{:?}", _other),
+    }
+  }
+
   pub fn decode_replay_details(&self, contents: Vec<u8>) -> Vec<EventEntry> {
     let mut decoder = VersionedDecoder::new(contents, &self.typeinfos);
     let details = decoder.instance(&self.typeinfos, &GAME_DETAILS_TYPEID, true);
@@ -603,14 +695,12 @@ impl<'a> Protocol<'a> {
     }
   }
 
-  pub fn decode_replay_tracker_events(&self, contents: Vec<u8>) -> Vec<Event> {
+  pub fn decode_replay_tracker_events(&self, contents: Vec<u8>, filter: &EventFilter) -> Vec<Event> {
     let mut decoder = VersionedDecoder::new(contents, &self.typeinfos);
     let mut gameloop = 0;
     let mut events: Vec<Event> = vec![];
 
     while !VersionedDecoder::done(&decoder.buffer) {
-      let start_bits = VersionedDecoder::used_bits(&decoder.buffer);
-
       let delta = decoder.instance(&self.typeinfos, &SVARUINT32_TYPEID, true);
       if let DecoderResult::Gameloop((_, v)) = delta {
         gameloop += v;
@@ -628,7 +718,7 @@ impl<'a> Protocol<'a> {
         None => panic!("CorruptedError: event_id({:?})", event_id),
       };
 
-      let is_event_allowed = ALLOWED_EVENTS.contains(typename);
+      let is_event_allowed = filter.allows(typename);
       let decoded_event = decoder.instance(&self.typeinfos, type_id, is_event_allowed);
       if is_event_allowed {
         let event = match decoded_event {
@@ -648,36 +738,83 @@ impl<'a> Protocol<'a> {
     events
   }
 
-  pub fn decode_replay_game_events(&self, contents: Vec<u8>) -> Vec<Event> {
+  pub fn decode_replay_initdata(&self, contents: Vec<u8>) -> Vec<EventEntry> {
+    let mut decoder = BitPackedDecoder::new(contents, &self.typeinfos);
+    let init_data = decoder.instance(&self.typeinfos, &REPLAY_INITDATA_TYPEID, true);
+
+    match init_data {
+      DecoderResult::Struct(values) => values,
+      _other => panic!("Found DecoderResult::{:?}", _other),
+    }
+  }
+
+  pub fn decode_replay_game_events(&self, contents: Vec<u8>, filter: &EventFilter) -> Vec<Event> {
+    self.decode_user_events(contents, filter, &GAME_EVENTID_TYPEID, &self.game_event_types)
+  }
+
+  // chat, pings and connection messages
+  pub fn decode_replay_message_events(&self, contents: Vec<u8>, filter: &EventFilter) -> Vec<Event> {
+    self.decode_user_events(contents, filter, &MESSAGE_EVENTID_TYPEID, &self.message_event_types)
+  }
+
+  // game and message events share a layout of gameloop delta, user id, event id then event
+  fn decode_user_events(
+    &self,
+    contents: Vec<u8>,
+    filter: &EventFilter,
+    eventid_typeid: &u8,
+    event_types: &EventTypes<'a>,
+  ) -> Vec<Event> {
     let mut decoder = BitPackedDecoder::new(contents, &self.typeinfos);
     let mut gameloop = 0;
     let mut events: Vec<Event> = vec![];
 
     while !BitPackedDecoder::done(&decoder.buffer) {
-      let start_bits = BitPackedDecoder::used_bits(&decoder.buffer);
-
       let delta = decoder.instance(&self.typeinfos, &SVARUINT32_TYPEID, true);
+      if let DecoderResult::Gameloop((_, v)) = delta {
+        gameloop += v;
+      } else {
+        panic!("found something else {:?}", delta);
+      }
 
-      let userid = decoder.instance(&self.typeinfos, &REPLAY_USERID_TYPEID, true);
+      let userid = match decoder.instance(&self.typeinfos, &REPLAY_USERID_TYPEID, true) {
+        DecoderResult::Struct(values) => match values.first() {
+          Some((_, DecoderResult::Value(userid))) => *userid,
+          _other => panic!("userid is not a value: {:?}", _other),
+        },
+        _other => panic!("userid is not a struct: {:?}", _other),
+      };
 
-      let event_id = match decoder.instance(&self.typeinfos, &GAME_EVENTID_TYPEID, true) {
+      let event_id = match decoder.instance(&self.typeinfos, eventid_typeid, true) {
         DecoderResult::Value(value) => value,
         _other => panic!("event_id is not a value: {:?}", _other),
       };
 
-      let (type_id, typename) = match self.game_event_types.get(&event_id) {
+      let (type_id, typename) = match event_types.get(&event_id) {
         Some((type_id, typename)) => (type_id, typename),
         None => panic!("CorruptedError: event_id({:?})", event_id),
       };
 
-      let event = match decoder.instance(&self.typeinfos, type_id, true) {
-        DecoderResult::Struct(mut entries) => {
-          entries.push(("_event".to_string(), DecoderResult::Name(typename.to_string())));
-          Event::new(entries)
-        }
-        _other => panic!("Only supports Structs"),
-      };
-      events.push(event);
+      let is_event_allowed = filter.allows(typename);
+      let decoded_event = decoder.instance(&self.typeinfos, type_id, is_event_allowed);
+      if is_event_allowed {
+        let event = match decoded_event {
+          DecoderResult::Struct(mut entries) => {
+            entries.push(("_gameloop".to_string(), DecoderResult::Value(gameloop)));
+            entries.push(("_userid".to_string(), DecoderResult::Value(userid)));
+            entries.push(("_event".to_string(), DecoderResult::Name(typename.to_string())));
+            Event::new(entries)
+          }
+          // events without any fields
+          DecoderResult::Null => Event::new(vec![
+            ("_gameloop".to_string(), DecoderResult::Value(gameloop)),
+            ("_userid".to_string(), DecoderResult::Value(userid)),
+            ("_event".to_string(), DecoderResult::Name(typename.to_string())),
+          ]),
+          _other => panic!("Only supports Structs"),
+        };
+        events.push(event);
+      }
 
       BitPackedDecoder::byte_align(&mut decoder.buffer);
     }
@@ -1,10 +1,14 @@
-use crate::decoders::{DecoderResult, EventEntry};
+use crate::Player;
+use crate::decoders::{find_field, find_value, DecoderResult, EventEntry};
 use crate::mpq::MPQArchive;
-use crate::protocol::Protocol;
+use crate::normalize::races::normalize_race;
+use crate::protocol::{EventFilter, Protocol, GAME_USER_LEAVE_EVENT};
+use crate::time::{Gameloop, PlayedAt};
 
 use serde::Deserialize;
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::time::Instant;
 
 #[derive(Debug)]
@@ -22,30 +26,76 @@ impl Event {
 
 #[derive(Debug, Deserialize)]
 pub struct PlayerMetadata<'a> {
-  pub PlayerID: u8,
-  pub APM: f32,
-  pub Result: &'a str,
-  pub SelectedRace: &'a str,
-  pub AssignedRace: &'a str,
+  #[serde(rename = "PlayerID")]
+  pub player_id: u8,
+  #[serde(rename = "APM")]
+  pub apm: f32,
+  #[serde(rename = "Result")]
+  pub result: &'a str,
+  #[serde(rename = "SelectedRace")]
+  pub selected_race: &'a str,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct Metadata<'a> {
-  pub Title: &'a str,
-  pub GameVersion: &'a str,
+  #[serde(rename = "GameVersion")]
+  pub game_version: &'a str,
   // pub DataBuild: &'a str,
   // pub DataVersion: &'a str,
   // pub BaseBuild: &'a str,
-  pub Duration: u16,
+  #[serde(rename = "Duration")]
+  pub duration: u16,
   // pub IsNotAvailable: bool,
-  pub Players: Vec<PlayerMetadata<'a>>,
+  #[serde(rename = "Players")]
+  pub players: Vec<PlayerMetadata<'a>>,
+}
+
+impl<'a> Metadata<'a> {
+  // older replays and some custom games have metadata without a result
+  pub fn has_result(&self) -> bool {
+    self.players.iter().any(|player| player.result == "Win" || player.result == "Tie")
+  }
+
+  // race picked in the lobby, including Random
+  pub fn selected_race(&self, player_id: u8) -> Option<&'a str> {
+    self.players
+      .iter()
+      .find(|player| player.player_id == player_id)
+      .map(|player| normalize_race(player.selected_race))
+  }
 }
 
 #[derive(Debug)]
 pub struct Parsed {
+  pub header: Vec<EventEntry>,
   pub player_info: Vec<EventEntry>,
   pub tracker_events: Vec<Event>,
-  pub metadata: String,
+  // only decoded when requested, or when leave events are
+  // needed to decide the result
+  pub game_events: Vec<Event>,
+  // decoded along with requested game events
+  pub message_events: Vec<Event>,
+  // maps game event user ids to player ids
+  pub user_players: HashMap<i64, u8>,
+  // player id -> whether their lobby slot picked Random
+  pub random_players: HashMap<u8, bool>,
+  // (width, height) in map cells
+  pub map_size: Option<(u16, u16)>,
+  // missing from older replays
+  pub metadata: Option<String>,
+  pub tags: String,
+}
+
+// lightweight summary of a replay which only reads the
+// header and details, used to filter replays before parsing events
+#[derive(Clone)]
+pub struct ReplayInfo {
+  pub version: String,
+  pub players: Vec<Player>,
+  pub map: String,
+  pub played_at: u64,
+  pub game_length: u16,
+  pub winner: Option<u8>,
   pub tags: String,
 }
 
@@ -56,13 +106,18 @@ pub struct Replay {
 }
 
 impl<'a> Replay {
-  pub fn new(file_path: PathBuf, content_hash: String, tags: Vec<&'a str>) -> Replay {
+  pub fn new(
+    file_path: PathBuf,
+    content_hash: String,
+    tags: Vec<&'a str>,
+    filter: &EventFilter,
+  ) -> Replay {
     let path_str = file_path.to_str().unwrap();
     println!("parsing replay {:?}", path_str);
 
-    let archive = MPQArchive::new(path_str);
+    let archive = MPQArchive::new(path_str).expect("Couldn't open replay archive");
     let protocol: Protocol = Protocol::new();
-    let parsed = Replay::parse(archive, protocol, tags);
+    let parsed = Replay::parse(archive, protocol, tags, filter);
 
     Replay {
       file_path: path_str.to_string(),
@@ -71,53 +126,324 @@ impl<'a> Replay {
     }
   }
 
-  fn parse (mut archive: MPQArchive, protocol: Protocol, tags: Vec<&'a str>) -> Parsed {
+  fn parse (
+    mut archive: MPQArchive,
+    protocol: Protocol,
+    tags: Vec<&'a str>,
+    filter: &EventFilter,
+  ) -> Parsed {
     let now = Instant::now();
 
-    // let header_content = &self.archive
-    //   .header
-    //   .user_data_header
-    //   .as_ref()
-    //   .expect("No user data header")
-    //   .content;
-    // // println!("read header {:.2?}", now.elapsed());
+    let header_content = archive
+      .header
+      .user_data_header
+      .as_ref()
+      .expect("No user data header")
+      .content
+      .clone();
+    let header = protocol.decode_replay_header(header_content);
+    // println!("read header {:.2?}", now.elapsed());
 
     let contents = archive.read_file("replay.tracker.events").unwrap();
     // println!("read tracker events {:.2?}", now.elapsed());
 
-    // let game_info = self.archive.read_file("replay.game.events").unwrap();
-    // // println!("read game events {:.2?}", now.elapsed());
-
-    // let init_data = self.archive.read_file("replay.initData").unwrap();
-    // // println!("read details {:.2?}", now.elapsed());
-
-    let raw_metadata = archive.read_file("replay.gamemetadata.json").unwrap();
-    let metadata = String::from_utf8(raw_metadata.clone()).unwrap();
+    let metadata = archive
+      .read_file("replay.gamemetadata.json")
+      .and_then(|raw_metadata| String::from_utf8(raw_metadata).ok());
     // println!("read metadata {:.2?}", now.elapsed());
 
     let details = archive.read_file("replay.details").unwrap();
     let player_info = protocol.decode_replay_details(details);
 
-    let tracker_events = protocol.decode_replay_tracker_events(contents);
+    let tracker_events = protocol.decode_replay_tracker_events(contents, filter);
     // println!("decoded replay tracker events {:.2?}", now.elapsed());
 
-    // let game_events = self.protocol.decode_replay_game_events(game_info);
-    // // println!("decoding replay game events {:.2?}", now.elapsed());
+    // leave events are needed for the result when metadata doesn't have one,
+    // and are always kept alongside other requested game events
+    let metadata_has_result = metadata
+      .as_deref()
+      .and_then(|metadata| serde_json::from_str::<Metadata>(metadata).ok())
+      .is_some_and(|metadata| metadata.has_result());
+    let mut game_event_filter = filter.clone();
+    game_event_filter.insert(GAME_USER_LEAVE_EVENT);
+
+    let mut game_events = vec![];
+    let mut message_events = vec![];
+    if filter.allows_game_events() || !metadata_has_result {
+      if let Some(game_info) = archive.read_file("replay.game.events") {
+        game_events = protocol.decode_replay_game_events(game_info, &game_event_filter);
+        // println!("decoded replay game events {:.2?}", now.elapsed());
+      }
+
+      if filter.allows_game_events() {
+        if let Some(messages) = archive.read_file("replay.message.events") {
+          message_events = protocol.decode_replay_message_events(messages, filter);
+        }
+      }
+    }
+
+    // lobby slots are needed for the selected race when metadata is missing
+    let mut user_players = HashMap::new();
+    let mut random_players = HashMap::new();
+    let mut map_size = None;
+    if let Some(init_data) = archive.read_file("replay.initData") {
+      let init_data = protocol.decode_replay_initdata(init_data);
+      user_players = Replay::user_players(&init_data, &player_info);
+      random_players = Replay::random_players(&init_data, &player_info);
+      map_size = Replay::map_size(&init_data);
+    }
 
     println!("parsed in {:.2?}", now.elapsed());
 
     Parsed {
+      header,
       player_info,
       tracker_events,
+      game_events,
+      message_events,
+      user_players,
+      random_players,
+      map_size,
       metadata,
       tags: tags.join(","),
     }
   }    
 
-  // // function that doesn't parse replay events for speed
-  // // can return high level information about game like
-  // // date, matchup, MMR, etc to decide whether to skip parsing
-  // pub fn peek() {
+  // lobby slots and details players share a working set slot id. replays
+  // without any fall back to user ids being assigned in player order.
+  // observers have lobby slots but aren't players
+  fn slot_players<'slot>(init_data: &'slot [EventEntry], details: &[EventEntry]) -> Vec<(&'slot Vec<EventEntry>, u8)> {
+    let mut slot_ids = HashMap::new();
+    if let Some((_, DecoderResult::Array(values))) = details
+      .iter()
+      .find(|(field, _)| *field == "m_playerList")
+    {
+      for (id, player) in values.iter().enumerate() {
+        if let DecoderResult::Struct(player_values) = player {
+          if let Some((_, DecoderResult::Value(slot_id))) = player_values
+            .iter()
+            .find(|(field, _)| *field == "m_workingSetSlotId")
+          {
+            slot_ids.insert(*slot_id, (id + 1) as u8);
+          }
+        }
+      }
+    }
 
-  // }
+    let slots = Replay::lobby_slots(init_data);
+    let has_slot_ids = slots
+      .iter()
+      .any(|slot| find_value(slot, "m_workingSetSlotId").is_some());
+
+    let mut slot_players = vec![];
+    for slot in slots {
+      let mut userid = None;
+      let mut slot_id = None;
+      let mut observe = 0;
+      for (field, value) in slot {
+        match (field.as_str(), value) {
+          ("m_userId", DecoderResult::Value(value)) => userid = Some(*value),
+          ("m_workingSetSlotId", DecoderResult::Value(value)) => slot_id = Some(*value),
+          ("m_observe", DecoderResult::Value(value)) => observe = *value,
+          _other => continue,
+        }
+      }
+
+      if observe != 0 {
+        continue;
+      }
+      let player_id = match has_slot_ids {
+        true => slot_id.and_then(|slot_id| slot_ids.get(&slot_id)).copied(),
+        false => userid.map(|userid| (userid + 1) as u8),
+      };
+      if let Some(player_id) = player_id {
+        slot_players.push((slot, player_id));
+      }
+    }
+
+    slot_players
+  }
+
+  // maps game event user ids to player ids, computer slots don't have a user id
+  fn user_players(init_data: &[EventEntry], details: &[EventEntry]) -> HashMap<i64, u8> {
+    let mut user_players = HashMap::new();
+    for (slot, player_id) in Replay::slot_players(init_data, details) {
+      if let Some(userid) = find_value(slot, "m_userId") {
+        user_players.insert(userid, player_id);
+      }
+    }
+
+    user_players
+  }
+
+  // m_racePref -> m_race is empty for slots that picked Random
+  fn random_players(init_data: &[EventEntry], details: &[EventEntry]) -> HashMap<u8, bool> {
+    let mut random_players = HashMap::new();
+    for (slot, player_id) in Replay::slot_players(init_data, details) {
+      if let Some(DecoderResult::Struct(race_pref)) = find_field(slot, "m_racePref") {
+        random_players.insert(player_id, find_value(race_pref, "m_race").is_none());
+      }
+    }
+
+    random_players
+  }
+
+  // m_syncLobbyState -> m_gameDescription -> m_mapSizeX and m_mapSizeY
+  fn map_size(init_data: &[EventEntry]) -> Option<(u16, u16)> {
+    let mut entries = init_data;
+    for name in ["m_syncLobbyState", "m_gameDescription"] {
+      entries = match entries.iter().find(|(field, _)| field == name) {
+        Some((_, DecoderResult::Struct(values))) => values,
+        _other => return None,
+      };
+    }
+
+    let width = find_value(entries, "m_mapSizeX")?;
+    let height = find_value(entries, "m_mapSizeY")?;
+    Some((width as u16, height as u16))
+  }
+
+  // m_syncLobbyState -> m_lobbyState -> m_slots
+  fn lobby_slots(init_data: &[EventEntry]) -> Vec<&Vec<EventEntry>> {
+    let mut entries = init_data;
+    for name in ["m_syncLobbyState", "m_lobbyState"] {
+      entries = match entries.iter().find(|(field, _)| field == name) {
+        Some((_, DecoderResult::Struct(values))) => values,
+        _other => return vec![],
+      };
+    }
+
+    match entries.iter().find(|(field, _)| *field == "m_slots") {
+      Some((_, DecoderResult::Array(slots))) => slots
+        .iter()
+        .filter_map(|slot| match slot {
+          DecoderResult::Struct(values) => Some(values),
+          _other => None,
+        })
+        .collect(),
+      _other => vec![],
+    }
+  }
+
+  pub fn elapsed_gameloops(header: &[EventEntry]) -> Gameloop {
+    match header.iter().find(|(field, _)| *field == "m_elapsedGameLoops") {
+      Some((_, DecoderResult::Value(gameloops))) => Gameloop(*gameloops as u32),
+      _other => Gameloop::default(),
+    }
+  }
+
+  // function that doesn't parse replay events for speed
+  // can return high level information about game like
+  // date, matchup, etc to decide whether to skip parsing.
+  // returns None for files that aren't replay archives or are truncated
+  // before their details. corrupted bytes in a complete archive still
+  // panic in the decoders
+  pub fn peek(file_path: &Path, tags: Vec<&'a str>) -> Option<ReplayInfo> {
+    let path_str = file_path.to_str()?;
+    let mut archive = MPQArchive::new(path_str).ok()?;
+    let protocol: Protocol = Protocol::new();
+
+    let header_content = archive
+      .header
+      .user_data_header
+      .as_ref()?
+      .content
+      .clone();
+    let header = protocol.decode_replay_header(header_content);
+
+    let mut version = String::new();
+    if let Some((_, DecoderResult::Struct(values))) = header
+      .iter()
+      .find(|(field, _)| *field == "m_version")
+    {
+      let version_parts: Vec<String> = ["m_major", "m_minor", "m_revision", "m_build"]
+        .iter()
+        .filter_map(|part| match values.iter().find(|(field, _)| field == part) {
+          Some((_, DecoderResult::Value(value))) => Some(value.to_string()),
+          _other => None,
+        })
+        .collect();
+      version = version_parts.join(".");
+    }
+
+    let game_length = Replay::elapsed_gameloops(&header).whole_seconds();
+
+    let details = archive.read_file("replay.details")?;
+    let player_info = protocol.decode_replay_details(details);
+
+    let raw_metadata = archive
+      .read_file("replay.gamemetadata.json")
+      .and_then(|raw_metadata| String::from_utf8(raw_metadata).ok());
+    let metadata: Option<Metadata> = raw_metadata
+      .as_deref()
+      .and_then(|raw_metadata| serde_json::from_str(raw_metadata).ok());
+
+    let mut map = String::new();
+    if let Some((_, DecoderResult::Blob(value))) = player_info
+      .iter()
+      .find(|(field, _)| *field == "m_title")
+    {
+      map = value.clone();
+    }
+
+    // invalid timestamps are left at 0 so date filters skip them
+    let played_at = PlayedAt::from_details(&player_info).unwrap_or_default();
+
+    let mut players = vec![];
+    let mut winner = None;
+    let values = match player_info.iter().find(|(field, _)| *field == "m_playerList") {
+      Some((_, DecoderResult::Array(values))) => values,
+      _other => return None,
+    };
+    for (id, player) in values.iter().enumerate() {
+      if let DecoderResult::Struct(player_values) = player {
+        let mut race = String::new();
+        let mut name = String::new();
+        let mut team = id as u8;
+        let mut observer = false;
+        for (field, value) in player_values {
+          match (field.as_str(), value) {
+            ("m_teamId", DecoderResult::Value(value)) => team = *value as u8,
+            ("m_observe", DecoderResult::Value(value)) => observer = *value != 0,
+            ("m_race", DecoderResult::Blob(value)) => race = normalize_race(value).to_string(),
+            ("m_name", DecoderResult::Blob(value)) => name = match value.find(">") {
+              Some(clan_tag_index) => value[clan_tag_index + 1..].to_string(),
+              None => value.clone(),
+            },
+            // 1 = win, 2 = loss, 3 = tie
+            ("m_result", DecoderResult::Value(1)) => winner = Some((id + 1) as u8),
+            _other => continue,
+          }
+        }
+
+        if observer {
+          continue;
+        }
+
+        // only metadata is read here, lobby slots are in initData
+        let selected_race = metadata
+          .as_ref()
+          .and_then(|metadata| metadata.selected_race((id + 1) as u8))
+          .map(|selected_race| selected_race.to_string());
+
+        players.push(Player {
+          id: (id + 1) as u8,
+          name,
+          race,
+          selected_race,
+          team,
+        });
+      }
+    }
+
+    Some(ReplayInfo {
+      version,
+      players,
+      map,
+      played_at: played_at.utc,
+      game_length,
+      winner,
+      tags: tags.join(","),
+    })
+  }
 }
@@ -0,0 +1,263 @@
+use crate::Player;
+use crate::decoders::{DecoderResult, EventEntry};
+use crate::replay::{Event, Metadata};
+
+use serde::Serialize;
+
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum GameResult {
+  // winning team
+  Win(u8),
+  Tie,
+  Unknown,
+}
+
+impl GameResult {
+  pub fn winning_team(&self) -> Option<u8> {
+    match self {
+      GameResult::Win(team) => Some(*team),
+      _other => None,
+    }
+  }
+
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      GameResult::Win(_) => "win",
+      GameResult::Tie => "tie",
+      GameResult::Unknown => "unknown",
+    }
+  }
+}
+
+// where the result was decided, in the order they are checked
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub enum ResultSource {
+  Metadata,
+  Details,
+  LeaveEvents,
+  None,
+}
+
+impl ResultSource {
+  pub fn as_str(&self) -> &'static str {
+    match self {
+      ResultSource::Metadata => "metadata",
+      ResultSource::Details => "details",
+      ResultSource::LeaveEvents => "leave_events",
+      ResultSource::None => "none",
+    }
+  }
+}
+
+// gamemetadata.json is missing from older replays, m_result in details
+// is often undecided in those too so leave events are the last resort
+pub fn decide_result(
+  metadata: Option<&Metadata>,
+  details: &[EventEntry],
+  leave_events: &[&Event],
+  user_players: &HashMap<i64, u8>,
+  players: &[Player],
+) -> (GameResult, ResultSource) {
+  if let Some(result) = metadata.and_then(|metadata| from_metadata(metadata, players)) {
+    return (result, ResultSource::Metadata);
+  }
+
+  if let Some(result) = from_details(details, players) {
+    return (result, ResultSource::Details);
+  }
+
+  if let Some(result) = from_leave_events(leave_events, user_players, players) {
+    return (result, ResultSource::LeaveEvents);
+  }
+
+  (GameResult::Unknown, ResultSource::None)
+}
+
+fn team(players: &[Player], player_id: u8) -> Option<u8> {
+  players
+    .iter()
+    .find(|player| player.id == player_id)
+    .map(|player| player.team)
+}
+
+fn from_metadata(metadata: &Metadata, players: &[Player]) -> Option<GameResult> {
+  if let Some(winner) = metadata.players.iter().find(|player| player.result == "Win") {
+    return team(players, winner.player_id).map(GameResult::Win);
+  }
+
+  if metadata.players.iter().any(|player| player.result == "Tie") {
+    return Some(GameResult::Tie);
+  }
+
+  None
+}
+
+fn from_details(details: &[EventEntry], players: &[Player]) -> Option<GameResult> {
+  let player_list = match details.iter().find(|(field, _)| *field == "m_playerList") {
+    Some((_, DecoderResult::Array(values))) => values,
+    _other => return None,
+  };
+
+  let mut tie = false;
+  for (id, player) in player_list.iter().enumerate() {
+    let player_values = match player {
+      DecoderResult::Struct(player_values) => player_values,
+      _other => continue,
+    };
+
+    // 0 = undecided, 1 = win, 2 = loss, 3 = tie
+    match player_values.iter().find(|(field, _)| *field == "m_result") {
+      Some((_, DecoderResult::Value(1))) => {
+        if let Some(team) = team(players, (id + 1) as u8) {
+          return Some(GameResult::Win(team));
+        }
+      },
+      Some((_, DecoderResult::Value(3))) => tie = true,
+      _other => continue,
+    }
+  }
+
+  if tie {
+    return Some(GameResult::Tie);
+  }
+
+  None
+}
+
+// players leave in order, the team left in the game once every
+// other team has gone is the winner
+fn from_leave_events(
+  leave_events: &[&Event],
+  user_players: &HashMap<i64, u8>,
+  players: &[Player],
+) -> Option<GameResult> {
+  let mut remaining: Vec<&Player> = players.iter().collect();
+
+  for event in leave_events {
+    let userid = match event.entries.iter().find(|(field, _)| *field == "_userid") {
+      Some((_, DecoderResult::Value(userid))) => *userid,
+      _other => continue,
+    };
+    let player_id = match user_players.get(&userid) {
+      Some(player_id) => *player_id,
+      None => continue,
+    };
+
+    remaining.retain(|player| player.id != player_id);
+
+    if let Some(first) = remaining.first() {
+      if remaining.iter().all(|player| player.team == first.team) {
+        return Some(GameResult::Win(first.team));
+      }
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::protocol::GAME_USER_LEAVE_EVENT;
+  use crate::replay::PlayerMetadata;
+
+  fn players() -> Vec<Player> {
+    (1..=2)
+      .map(|id| Player {
+        id,
+        name: format!("player{id}"),
+        race: "Terran".to_string(),
+        selected_race: Some("Terran".to_string()),
+        team: id,
+      })
+      .collect()
+  }
+
+  fn metadata<'a>(results: [&'a str; 2]) -> Metadata<'a> {
+    Metadata {
+      game_version: "5.0.11.89720",
+      duration: 600,
+      players: results
+        .iter()
+        .enumerate()
+        .map(|(index, result)| PlayerMetadata {
+          player_id: index as u8 + 1,
+          apm: 200.0,
+          result,
+          selected_race: "Terr",
+        })
+        .collect(),
+    }
+  }
+
+  // m_result of each player, 0 = undecided, 1 = win, 2 = loss, 3 = tie
+  fn details(results: [i64; 2]) -> Vec<EventEntry> {
+    let player_list = results
+      .iter()
+      .map(|result| DecoderResult::Struct(vec![("m_result".to_string(), DecoderResult::Value(*result))]))
+      .collect();
+    vec![("m_playerList".to_string(), DecoderResult::Array(player_list))]
+  }
+
+  fn leave_event(userid: i64) -> Event {
+    Event::new(vec![
+      ("_event".to_string(), DecoderResult::Name(GAME_USER_LEAVE_EVENT.to_string())),
+      ("_userid".to_string(), DecoderResult::Value(userid)),
+    ])
+  }
+
+  fn user_players() -> HashMap<i64, u8> {
+    HashMap::from([(0, 1), (1, 2)])
+  }
+
+  #[test]
+  fn decides_from_metadata_first() {
+    let metadata = metadata(["Loss", "Win"]);
+    let result = decide_result(Some(&metadata), &details([1, 2]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Win(2), ResultSource::Metadata));
+  }
+
+  #[test]
+  fn falls_back_to_details_without_metadata_result() {
+    let metadata = metadata(["Undecided", "Undecided"]);
+    let result = decide_result(Some(&metadata), &details([1, 2]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Win(1), ResultSource::Details));
+
+    let result = decide_result(None, &details([2, 1]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Win(2), ResultSource::Details));
+  }
+
+  #[test]
+  fn falls_back_to_leave_events() {
+    let leave = leave_event(0);
+    let result = decide_result(None, &details([0, 0]), &[&leave], &user_players(), &players());
+    assert_eq!(result, (GameResult::Win(2), ResultSource::LeaveEvents));
+  }
+
+  #[test]
+  fn ignores_leave_events_of_unknown_users() {
+    // observers aren't in user_players
+    let leave = leave_event(5);
+    let result = decide_result(None, &details([0, 0]), &[&leave], &user_players(), &players());
+    assert_eq!(result, (GameResult::Unknown, ResultSource::None));
+  }
+
+  #[test]
+  fn decides_ties() {
+    let metadata = metadata(["Tie", "Tie"]);
+    let result = decide_result(Some(&metadata), &details([0, 0]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Tie, ResultSource::Metadata));
+
+    let result = decide_result(None, &details([3, 3]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Tie, ResultSource::Details));
+  }
+
+  #[test]
+  fn unknown_without_any_result() {
+    let result = decide_result(None, &details([0, 0]), &[], &user_players(), &players());
+    assert_eq!(result, (GameResult::Unknown, ResultSource::None));
+    assert_eq!(result.0.winning_team(), None);
+  }
+}
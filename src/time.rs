@@ -0,0 +1,149 @@
+use crate::decoders::{DecoderResult, EventEntry};
+
+use serde::Serialize;
+
+use std::ops::{Add, Sub};
+
+// games are recorded at "faster" speed, 16 gameloops per game second * 1.4
+pub const GAMELOOPS_PER_SECOND: f32 = 22.4;
+
+// windows FILETIME is in 100ns ticks since 1st Jan 1601
+// https://en.wikipedia.org/wiki/Epoch_(computing)
+const FILETIME_TICKS_PER_SECOND: i64 = 10_000_000;
+const FILETIME_UNIX_EPOCH_SECONDS: i64 = 11_644_473_600;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct Gameloop(pub u32);
+
+impl Gameloop {
+  // 22.4 = 112 / 5, kept as integers so it can be used in consts
+  pub const fn from_seconds(seconds: u32) -> Gameloop {
+    Gameloop(seconds * 112 / 5)
+  }
+
+  pub fn seconds(&self) -> f32 {
+    self.0 as f32 / GAMELOOPS_PER_SECOND
+  }
+
+  // rounded down, like the in-game timer
+  pub fn whole_seconds(&self) -> u16 {
+    (self.0 * 5 / 112).min(u16::MAX as u32) as u16
+  }
+
+  pub fn saturating_sub(self, other: Gameloop) -> Gameloop {
+    Gameloop(self.0.saturating_sub(other.0))
+  }
+}
+
+impl Add for Gameloop {
+  type Output = Gameloop;
+
+  fn add(self, other: Gameloop) -> Gameloop {
+    Gameloop(self.0 + other.0)
+  }
+}
+
+impl Sub for Gameloop {
+  type Output = Gameloop;
+
+  fn sub(self, other: Gameloop) -> Gameloop {
+    Gameloop(self.0 - other.0)
+  }
+}
+
+impl From<u16> for Gameloop {
+  fn from(gameloop: u16) -> Gameloop {
+    Gameloop(gameloop as u32)
+  }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct PlayedAt {
+  // unix timestamp
+  pub utc: u64,
+  // seconds east of UTC on the machine that recorded the replay
+  pub local_offset: i64,
+}
+
+impl PlayedAt {
+  // returns None for times before the unix epoch, which are
+  // zeroed or corrupted values rather than real games
+  pub fn from_filetime(utc: i64, local_offset: i64) -> Option<PlayedAt> {
+    let unix = (utc / FILETIME_TICKS_PER_SECOND).checked_sub(FILETIME_UNIX_EPOCH_SECONDS)?;
+    if unix < 0 {
+      return None;
+    }
+
+    Some(PlayedAt {
+      utc: unix as u64,
+      local_offset: local_offset / FILETIME_TICKS_PER_SECOND,
+    })
+  }
+
+  // reads m_timeUTC and m_timeLocalOffset from replay details
+  pub fn from_details(details: &[EventEntry]) -> Option<PlayedAt> {
+    let mut utc = None;
+    let mut local_offset = 0;
+    for (field, value) in details {
+      match (field.as_str(), value) {
+        ("m_timeUTC", DecoderResult::Value(value)) => utc = Some(*value),
+        ("m_timeLocalOffset", DecoderResult::Value(value)) => local_offset = *value,
+        _other => continue,
+      }
+    }
+
+    PlayedAt::from_filetime(utc?, local_offset)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn converts_seconds_to_gameloops() {
+    assert_eq!(Gameloop::from_seconds(0), Gameloop(0));
+    assert_eq!(Gameloop::from_seconds(5), Gameloop(112));
+    assert_eq!(Gameloop::from_seconds(60), Gameloop(1344));
+    assert_eq!(Gameloop(1344).whole_seconds(), 60);
+    assert_eq!(Gameloop(1343).whole_seconds(), 59);
+  }
+
+  #[test]
+  fn gameloops_past_u16_max() {
+    let gameloop = Gameloop::from_seconds(3600);
+    assert_eq!(gameloop, Gameloop(80_640));
+    assert_eq!(gameloop.whole_seconds(), 3600);
+    assert_eq!((gameloop - Gameloop(65_536)).0, 15_104);
+    assert_eq!(Gameloop(10).saturating_sub(gameloop), Gameloop(0));
+  }
+
+  #[test]
+  fn converts_filetime_to_unix() {
+    // 2020-01-01T00:00:00Z
+    let utc = (1_577_836_800 + FILETIME_UNIX_EPOCH_SECONDS) * FILETIME_TICKS_PER_SECOND;
+    let played_at = PlayedAt::from_filetime(utc, 0).unwrap();
+    assert_eq!(played_at.utc, 1_577_836_800);
+    assert_eq!(played_at.local_offset, 0);
+
+    let unix_epoch = FILETIME_UNIX_EPOCH_SECONDS * FILETIME_TICKS_PER_SECOND;
+    assert_eq!(PlayedAt::from_filetime(unix_epoch, 0).unwrap().utc, 0);
+  }
+
+  #[test]
+  fn converts_local_offset_to_seconds() {
+    let utc = (1_577_836_800 + FILETIME_UNIX_EPOCH_SECONDS) * FILETIME_TICKS_PER_SECOND;
+
+    // UTC+2 and UTC-5
+    let east = PlayedAt::from_filetime(utc, 7200 * FILETIME_TICKS_PER_SECOND).unwrap();
+    assert_eq!(east.local_offset, 7200);
+    let west = PlayedAt::from_filetime(utc, -18_000 * FILETIME_TICKS_PER_SECOND).unwrap();
+    assert_eq!(west.local_offset, -18_000);
+  }
+
+  #[test]
+  fn rejects_times_before_unix_epoch() {
+    assert_eq!(PlayedAt::from_filetime(0, 0), None);
+    assert_eq!(PlayedAt::from_filetime(FILETIME_TICKS_PER_SECOND, 0), None);
+  }
+}
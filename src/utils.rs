@@ -1,19 +1,13 @@
 use crate::replay::Replay;
-use crate::decoders::DecoderResult;
+use crate::protocol::EventFilter;
 
-use std::fs::{copy, read_dir};
+use std::fs::read_dir;
 use std::io::Result;
 use std::path::Path;
 
-use serde::{Serialize, Deserialize};
-use sha256::digest_file;
+use sha256::try_digest;
 
-#[derive(Serialize, Deserialize)]
-struct Manifest {
-  content_hashes: Vec<String>,
-}
-
-pub fn visit_dirs(replays: &mut Vec<Replay>, dir: &Path) -> Result<()> {
+pub fn visit_dirs(replays: &mut Vec<Replay>, dir: &Path, filter: &EventFilter) -> Result<()> {
   const VALID_TAGS: [&str; 10] = [
     "ASUS ROG",
     "DreamHack Masters",
@@ -33,7 +27,7 @@ pub fn visit_dirs(replays: &mut Vec<Replay>, dir: &Path) -> Result<()> {
       let path = entry.path();
       // let filename = entry.file_name();
       if path.is_dir() && !path.to_str().unwrap().contains("PiG") {
-        visit_dirs(replays, &entry.path())?;
+        visit_dirs(replays, &entry.path(), filter)?;
       }
 
       match path.extension() {
@@ -48,31 +42,15 @@ pub fn visit_dirs(replays: &mut Vec<Replay>, dir: &Path) -> Result<()> {
               }
             }
 
-            let content_hash = digest_file(&path).expect("Replay file should be hashed");
-
-            // let bucket_path = format!("/Users/lukeholroyd/Desktop/replays/bucket/{content_hash}.SC2Replay");
-            // println!("copying replay file to new bucket path: {:?}", bucket_path);
-            // copy(
-            //   &path,
-            //   bucket_path,
-            // ).expect("Replay file is copied from existing file structure into bucket structure");
-
-            let replay = Replay::new(path, content_hash, tags);
-
-            let raw_played_at = &replay.parsed.player_info
-              .iter()
-              .find(|(field, _)| *field == "m_timeUTC")
-              .unwrap().1;
-            let mut played_at = 0;
-            if let DecoderResult::Value(value) = raw_played_at {
-              // TODO: this truncation is not working properly
-              played_at = value.clone() as u64;
-            }
-            // game records time in window epoch for some reason
-            // https://en.wikipedia.org/wiki/Epoch_(computing)
-            played_at = (played_at / 10000000) - 11644473600;
-
-            // replays.push(replay);
+            // only read header and details to check date before parsing events
+            let info = match Replay::peek(&path, tags.clone()) {
+              Some(info) => info,
+              None => {
+                println!("skipping unreadable replay {:?}", path);
+                continue;
+              },
+            };
+            let played_at = info.played_at;
 
             // 1st Jan 2023 1672531200
             // 1st Jan 2022 1640995200
@@ -80,9 +58,39 @@ pub fn visit_dirs(replays: &mut Vec<Replay>, dir: &Path) -> Result<()> {
             // 1st Jan 2020 1577836800
             // 1st Jan 2019 1546300800
             // 1st Jan 2018 1514764800
-            if played_at >= 1640995200 && played_at < 1672531200 {
-              replays.push(replay);
+            if !(1640995200..1672531200).contains(&played_at) {
+              continue;
             }
+
+            // the parser needs a player on another team to compare against
+            let first_team = info.players.first().map(|player| player.team);
+            if !info.players.iter().any(|player| Some(player.team) != first_team) {
+              println!("skipping replay without opponents {:?}", path);
+              continue;
+            }
+
+            let races: Vec<&str> = info.players.iter().map(|player| player.race.as_str()).collect();
+            println!(
+              "found {} on {} ({}, {}s, winner {:?}) {}",
+              races.join("v"),
+              info.map,
+              info.version,
+              info.game_length,
+              info.winner,
+              info.tags,
+            );
+
+            let content_hash = try_digest(path.as_path()).expect("Replay file should be hashed");
+
+            // let bucket_path = format!("/Users/lukeholroyd/Desktop/replays/bucket/{content_hash}.SC2Replay");
+            // println!("copying replay file to new bucket path: {:?}", bucket_path);
+            // copy(
+            //   &path,
+            //   bucket_path,
+            // ).expect("Replay file is copied from existing file structure into bucket structure");
+
+            let replay = Replay::new(path, content_hash, tags, filter);
+            replays.push(replay);
           }
         },
         None => continue,